@@ -1,37 +1,187 @@
-use image::{RgbaImage, codecs::png::PngEncoder};
+use image::{Rgba, RgbaImage, codecs::png::PngEncoder};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::{
-  fs::File,
-  io::{Read, Seek, SeekFrom},
-};
+use std::collections::BTreeMap;
 
 use crate::{
-  chr::{flip_x, flip_y, read_bytes, transpose},
-  pal::{ChrPalette, read_palette_from_bytes},
-  render::append_pattern_on_image,
+  binary::{read_binary, read_region},
+  chr::{flip_x, flip_y, transpose},
+  codec::{TileFormat, TilePattern, read_tiles},
+  compression::{Compression, Decompressed},
+  fds::FdsImage,
+  nametable::{NAMETABLE_HEIGHT, NAMETABLE_WIDTH, Nametable},
+  pal::PaletteFormat,
+  ppu::{PpuMask, PpuType},
+  render::append_tile_on_image,
 };
 
 /// allows complex rendering of tiles or sprites from a chr binary
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug)]
 pub struct Atlas {
+  /// main chr source
+  #[cfg_attr(feature = "serde", serde(flatten))]
+  pub source: AtlasSource,
+  /// additional chr sources, referenced by name in [`AtlasData::source`]
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub sources: BTreeMap<String, AtlasSource>,
+  /// palette file, in the format given by [`Atlas::palette_format`]
+  ///
+  /// ignored when [`Atlas::ppu`] is an rgb ppu
+  pub palette: String,
+  /// format of the palette file
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub palette_format: PaletteFormat,
+  /// ppu revision, rgb ppus use their built-in palette
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub ppu: PpuType,
+  /// PPUMASK colour effects applied to the palette
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub mask: PpuMask,
+  /// atlas data, contains every single tile to draw
+  pub data: Vec<AtlasData>,
+  /// background layer
+  ///
+  /// when set, atlas data are composited like sprites in OAM: earlier data are drawn in front of
+  /// later data, color 0 is always transparent, and data behind the background are only visible
+  /// where the background is transparent
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub background: Option<AtlasBackground>,
+  /// anchors of the groups of data, referenced by name in [`AtlasData::group`]
+  ///
+  /// groups without an anchor are anchored on the top left corner of their data
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub groups: BTreeMap<String, AtlasGroup>,
+  /// animations, made of groups of data
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub animations: BTreeMap<String, AtlasAnimation>,
+}
+
+/// a region of a binary to read chrs from
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug)]
+pub struct AtlasSource {
   /// binary to source from
+  ///
+  /// can point into a zip archive, see [`split_archive_path`](crate::binary::split_archive_path)
   pub binary: String,
-  /// palette, must be a .pal file format
-  pub palette: String,
   /// which address to start from
   pub start: u64,
   /// how many bytes to read
+  ///
+  /// ignored when [`AtlasSource::banks`] is not empty
   pub length: usize,
-  /// atlas data, contains every single tile to draw
-  pub data: Vec<AtlasData>,
+  /// chr bank windows, assembles a virtual pattern table out of several banks of the binary
+  ///
+  /// when set, `chr_index` addresses the assembled pattern table
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub banks: Vec<AtlasBank>,
+  /// format of the tiles
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub format: TileFormat,
+  /// file of a .fds image to read from
+  ///
+  /// when set, `start` and the banks address the data of the file instead of the binary
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub fds: Option<AtlasFdsFile>,
+  /// compression of the tiles
+  ///
+  /// when set, the compressed stream starts at `start`, and `length` and the banks address the
+  /// decompressed data
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub compression: Option<Compression>,
 }
 
-/// contains data for drawing one 8x8 tile
+/// a file of a Famicom Disk System image
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug)]
+pub struct AtlasFdsFile {
+  /// disk side, 0 being side A of the first disk, 1 side B, 2 side A of the second disk...
+  pub side: usize,
+  /// index of the file in the side
+  pub file: usize,
+}
+
+/// a chr bank mapped into the virtual pattern table of an atlas
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug)]
+pub struct AtlasBank {
+  /// size of the bank in bytes, usually 0x400, 0x800 or 0x1000
+  pub size: usize,
+  /// bank number, counted in bank sizes from the start address
+  pub bank: usize,
+  /// slot of the pattern table the bank is mapped to, counted in bank sizes
+  pub slot: usize,
+}
+
+/// anchor of a group of atlas data, like the sprites of a metasprite
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AtlasGroup {
+  /// x position of the anchor
+  pub x: u32,
+  /// y position of the anchor
+  pub y: u32,
+}
+
+/// an animation, showing groups of atlas data one after the other
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasAnimation {
+  /// names of the groups of the frames, see [`AtlasData::group`]
+  pub frames: Vec<String>,
+  /// frames per second
+  pub speed: f64,
+  /// whether the animation starts over after its last frame
+  pub looping: bool,
+}
+
+impl Default for AtlasAnimation {
+  fn default() -> Self {
+    Self {
+      frames: Vec::new(),
+      speed: 5.0,
+      looping: true,
+    }
+  }
+}
+
+/// background layer of an atlas
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Debug)]
+pub enum AtlasBackground {
+  /// plain backdrop colour
+  Backdrop(usize),
+  /// nametable rendered with the chrs of the atlas
+  Nametable(AtlasNametable),
+}
+
+/// nametable used as the background of an atlas
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug)]
+pub struct AtlasNametable {
+  /// binary containing the nametable
+  pub binary: String,
+  /// address of the nametable (tiles followed by attributes, 1024 bytes)
+  pub start: u64,
+  /// index of the chr used for tile 0 of the nametable
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub chr_offset: usize,
+  /// backdrop colour, shown where the background is transparent
+  pub backdrop: usize,
+  /// the four background sub-palettes (colors 1 to 3)
+  pub palettes: [[usize; 3]; 4],
+}
+
+/// contains data for drawing one 8x8 tile
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default)]
 pub struct AtlasData {
+  /// name of the source of the tile, the main source if `None`
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub source: Option<String>,
   /// index of the tile
   pub chr_index: usize,
   /// color 0
@@ -40,6 +190,17 @@ pub struct AtlasData {
   pub c1: usize,
   /// color 2
   pub c2: usize,
+  /// colors of pixel values 1 and up, replaces c0, c1 and c2 when not empty
+  ///
+  /// needed by formats with more than 4 colors
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub colors: Vec<usize>,
+  /// palette line, replaces the other colors when set
+  ///
+  /// pixel value `v` uses palette color `line * 2^bpp + v`, like the 16 colour lines of the
+  /// Master System and Genesis
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub line: Option<usize>,
   /// x position
   pub x: u32,
   /// y position
@@ -55,28 +216,271 @@ pub struct AtlasData {
   pub flip_y: bool,
   /// color background
   pub cbg: Option<usize>,
+  /// sprite priority, draws the tile behind opaque background pixels
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub behind_background: bool,
+  /// sprite sub-palette of the data, found from its colours when `None`
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub sub_palette: Option<usize>,
+  /// name of the group of the data, like a metasprite
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub group: Option<String>,
+}
+
+/// returns `length` bytes of `b` starting at `start`
+fn slice_region(b: &[u8], start: usize, length: usize) -> Result<Vec<u8>, crate::Error> {
+  b.get(start..start + length)
+    .map(|d| d.to_vec())
+    .ok_or(crate::Error::IOError(
+      std::io::ErrorKind::UnexpectedEof.into(),
+    ))
+}
+
+impl AtlasSource {
+  /// reads the whole binary, or the whole fds file if there is one
+  fn read_all(&self) -> Result<Vec<u8>, crate::Error> {
+    let binary = read_binary(&self.binary)?;
+    match &self.fds {
+      None => Ok(binary),
+      Some(f) => Ok(
+        FdsImage::from_bytes(&binary)?
+          .file(f.side, f.file)?
+          .data
+          .clone(),
+      ),
+    }
+  }
+
+  /// reads a region of the binary, or of the fds file if there is one
+  fn read(&self, start: u64, length: usize) -> Result<Vec<u8>, crate::Error> {
+    match &self.fds {
+      None => read_region(&self.binary, start, length),
+      Some(_) => slice_region(&self.read_all()?, start as usize, length),
+    }
+  }
+
+  /// size of the data read by the source, `length` or the end of the last bank
+  fn data_size(&self) -> usize {
+    match self.banks.is_empty() {
+      true => self.length,
+      false => self
+        .banks
+        .iter()
+        .fold(0, |acc, b| acc.max((b.bank + 1) * b.size)),
+    }
+  }
+
+  /// decompresses the data of the source, returns `None` if it is not compressed
+  ///
+  /// [`Decompressed::consumed`] gives the size of the compressed stream
+  pub fn decompress(&self) -> Result<Option<Decompressed>, crate::Error> {
+    let Some(compression) = self.compression else {
+      return Ok(None);
+    };
+
+    let b = self.read_all()?;
+    let stream = b.get(self.start as usize..).ok_or(crate::Error::IOError(
+      std::io::ErrorKind::UnexpectedEof.into(),
+    ))?;
+    compression.decompress(stream, self.data_size()).map(Some)
+  }
+
+  /// reads the chrs of the source from its binary
+  pub fn get_chrs(&self) -> Result<Vec<TilePattern>, crate::Error> {
+    read_tiles(&self.get_pattern_table()?, &*self.format.codec())
+  }
+
+  /// reads the bytes of the pattern table, assembling bank windows if there are any
+  pub fn get_pattern_table(&self) -> Result<Vec<u8>, crate::Error> {
+    let decompressed = self.decompress()?;
+    // reads a region of the data, relative to `start`
+    let region = |offset: usize, length: usize| match &decompressed {
+      Some(d) => slice_region(&d.data, offset, length),
+      None => self.read(self.start + offset as u64, length),
+    };
+
+    if self.banks.is_empty() {
+      return region(0, self.length);
+    }
+
+    let size = self
+      .banks
+      .iter()
+      .fold(0, |acc, b| acc.max((b.slot + 1) * b.size));
+    let mut table = vec![0; size];
+
+    for b in &self.banks {
+      let bank = region(b.bank * b.size, b.size)?;
+      table[b.slot * b.size..(b.slot + 1) * b.size].copy_from_slice(&bank);
+    }
+
+    Ok(table)
+  }
+}
+
+/// decoded chrs of every source of an atlas
+#[derive(Clone, Debug)]
+pub struct AtlasChrs {
+  /// chrs of the main source
+  pub main: Vec<TilePattern>,
+  /// chrs of the named sources
+  pub sources: BTreeMap<String, Vec<TilePattern>>,
+}
+
+impl AtlasChrs {
+  /// returns the chrs of a source, the main source if `None`
+  pub fn source(&self, name: Option<&str>) -> Result<&[TilePattern], crate::Error> {
+    match name {
+      None => Ok(&self.main),
+      Some(n) => self
+        .sources
+        .get(n)
+        .map(|c| c.as_slice())
+        .ok_or(crate::Error::AtlasUnknownSourceError(n.to_string())),
+    }
+  }
+
+  /// returns the chr of a data
+  pub fn get(&self, d: &AtlasData) -> Result<TilePattern, crate::Error> {
+    self
+      .source(d.source.as_deref())?
+      .get(d.chr_index)
+      .copied()
+      .ok_or(crate::Error::AtlasWrongIndexError(d.chr_index))
+  }
+}
+
+impl AtlasData {
+  /// returns the colors of pixel values 1 and up, for a chr of `bits_per_pixel`
+  pub fn get_colors(&self, bits_per_pixel: usize) -> Vec<usize> {
+    if let Some(line) = self.line {
+      let size = 1 << bits_per_pixel;
+      return (1..size).map(|v| line * size + v).collect();
+    }
+
+    match self.colors.is_empty() {
+      true => vec![self.c0, self.c1, self.c2],
+      false => self.colors.clone(),
+    }
+  }
+}
+
+/// returns the highest pixel value of a chr, which is the number of colors it needs
+fn needed_colors(chr: &TilePattern) -> usize {
+  chr.iter().flatten().max().copied().unwrap_or(0) as usize
+}
+
+impl AtlasNametable {
+  /// reads the nametable from its binary
+  pub fn get_nametable(&self) -> Result<Nametable, crate::Error> {
+    Nametable::from_bytes(&read_region(&self.binary, self.start, 1024)?)
+  }
 }
 
 impl Atlas {
-  fn get_image(&self) -> Result<RgbaImage, crate::Error> {
-    let mut chr_file = File::open(&self.binary).map_err(crate::Error::IOError)?;
-    chr_file
-      .seek(SeekFrom::Start(self.start))
-      .map_err(crate::Error::IOError)?;
-    let mut chr_buf = vec![0; self.length];
-    chr_file
-      .read_exact(&mut chr_buf)
-      .map_err(crate::Error::IOError)?;
+  /// returns the ppu revision, reading it from the binary's header if needed
+  pub fn get_ppu(&self) -> Result<PpuType, crate::Error> {
+    match self.ppu {
+      PpuType::Header => {
+        let binary = read_binary(&self.source.binary)?;
+        let header = &binary[..binary.len().min(16)];
+
+        Ok(PpuType::from_nes_header(header).unwrap_or_default())
+      }
+      ppu => Ok(ppu),
+    }
+  }
+
+  /// returns the colours of the atlas, indexed by the atlas data colours
+  ///
+  /// the [`Atlas::mask`] effects are already applied
+  pub fn get_palette(&self) -> Result<Vec<[u8; 3]>, crate::Error> {
+    let ppu = self.get_ppu()?;
+
+    let pal = match ppu.builtin_palette() {
+      Some(pal) => pal,
+      None => self.palette_format.read(read_binary(&self.palette)?)?,
+    };
+
+    Ok(ppu.apply_mask(&pal, self.mask))
+  }
 
-    let chrs = read_bytes(chr_buf.to_vec())?;
+  /// reads the chrs of the main source
+  pub fn get_chrs(&self) -> Result<Vec<TilePattern>, crate::Error> {
+    self.source.get_chrs()
+  }
+
+  /// returns a source by name, the main source if `None`
+  pub fn get_source(&self, name: Option<&str>) -> Result<&AtlasSource, crate::Error> {
+    match name {
+      None => Ok(&self.source),
+      Some(n) => self
+        .sources
+        .get(n)
+        .ok_or(crate::Error::AtlasUnknownSourceError(n.to_string())),
+    }
+  }
+
+  /// reads the chrs of every source
+  pub fn get_all_chrs(&self) -> Result<AtlasChrs, crate::Error> {
+    let mut sources = BTreeMap::new();
+    for (name, source) in &self.sources {
+      sources.insert(name.clone(), source.get_chrs()?);
+    }
+
+    Ok(AtlasChrs {
+      main: self.get_chrs()?,
+      sources,
+    })
+  }
+
+  /// returns the chr of a data, with its transposition and flips applied
+  fn get_data_chr(chrs: &AtlasChrs, d: &AtlasData) -> Result<TilePattern, crate::Error> {
+    let mut chr = chrs.get(d)?;
+    if d.transpose {
+      transpose(&mut chr);
+    }
+    if d.flip_x {
+      flip_x(&mut chr);
+    }
+    if d.flip_y {
+      flip_y(&mut chr);
+    }
+    Ok(chr)
+  }
+
+  /// returns the colors of pixel values 1 and up of a data, using the format of its source
+  pub fn get_colors(&self, d: &AtlasData) -> Result<Vec<usize>, crate::Error> {
+    let source = self.get_source(d.source.as_deref())?;
+    Ok(d.get_colors(source.format.codec().bits_per_pixel()))
+  }
+
+  /// returns the colors of a data, checking that there are enough for its chr
+  fn get_data_colors(
+    &self,
+    d: &AtlasData,
+    chr: &TilePattern,
+    pal: &[[u8; 3]],
+  ) -> Result<Vec<[u8; 3]>, crate::Error> {
+    let colors = self.get_colors(d)?;
+    if let Some(c) = colors.iter().find(|c| **c >= pal.len()) {
+      return Err(crate::Error::AtlasPaletteIndexError(*c, pal.len()));
+    }
+    let colors: Vec<[u8; 3]> = colors.iter().map(|c| pal[*c]).collect();
+    let needed = needed_colors(chr);
+    if needed > colors.len() {
+      return Err(crate::Error::AtlasColorsError(d.chr_index, needed));
+    }
+    Ok(colors)
+  }
 
-    let mut pal_file = File::open(&self.palette).map_err(crate::Error::IOError)?;
-    let mut pal_buf = Vec::new();
-    pal_file
-      .read_to_end(&mut pal_buf)
-      .map_err(crate::Error::IOError)?;
+  fn get_image(&self) -> Result<RgbaImage, crate::Error> {
+    let chrs = self.get_all_chrs()?;
+    let pal = self.get_palette()?;
 
-    let pal = read_palette_from_bytes(pal_buf)?;
+    if let Some(background) = &self.background {
+      return self.get_composited_image(background, &chrs, &pal);
+    }
 
     let img_size = self
       .data
@@ -85,36 +489,96 @@ impl Atlas {
     let mut img = RgbaImage::new(img_size.0, img_size.1);
 
     for d in &self.data {
-      if d.chr_index >= chrs.len() {
-        return Err(crate::Error::AtlasWrongIndexError(d.chr_index));
-      }
+      let chr = Self::get_data_chr(&chrs, d)?;
+      let colors = self.get_data_colors(d, &chr, &pal)?;
 
-      let mut chr = chrs[d.chr_index];
-      if d.transpose {
-        transpose(&mut chr);
-      }
-      if d.flip_x {
-        flip_x(&mut chr);
+      append_tile_on_image(&mut img, &chr, d.x, d.y, &colors, d.cbg.map(|c| pal[c]));
+    }
+
+    Ok(img)
+  }
+
+  fn get_composited_image(
+    &self,
+    background: &AtlasBackground,
+    chrs: &AtlasChrs,
+    pal: &[[u8; 3]],
+  ) -> Result<RgbaImage, crate::Error> {
+    let (mut width, mut height) = self.data.iter().fold((0, 0), |acc, d| {
+      (acc.0.max(d.x as usize + 8), acc.1.max(d.y as usize + 8))
+    });
+
+    let (backdrop, nametable) = match background {
+      AtlasBackground::Backdrop(c) => (*c, None),
+      AtlasBackground::Nametable(n) => {
+        width = width.max(NAMETABLE_WIDTH * 8);
+        height = height.max(NAMETABLE_HEIGHT * 8);
+        (n.backdrop, Some((n, n.get_nametable()?)))
       }
-      if d.flip_y {
-        flip_y(&mut chr);
+    };
+
+    let background_colors = nametable
+      .iter()
+      .flat_map(|(n, _)| n.palettes.iter().flatten());
+    if let Some(c) = std::iter::once(&backdrop)
+      .chain(background_colors)
+      .find(|c| **c >= pal.len())
+    {
+      return Err(crate::Error::AtlasPaletteIndexError(*c, pal.len()));
+    }
+
+    // background layer: colour, and whether the pixel is opaque
+    let mut bg = vec![(pal[backdrop], false); width * height];
+    if let Some((n, nt)) = nametable {
+      for ty in 0..NAMETABLE_HEIGHT {
+        for tx in 0..NAMETABLE_WIDTH {
+          let index = n.chr_offset + nt.tile(tx, ty) as usize;
+          let chr = chrs
+            .main
+            .get(index)
+            .ok_or(crate::Error::AtlasWrongIndexError(index))?;
+          if needed_colors(chr) > 3 {
+            return Err(crate::Error::AtlasColorsError(index, needed_colors(chr)));
+          }
+          let sub_palette = n.palettes[nt.sub_palette(tx, ty)];
+
+          for (y, row) in chr.iter().enumerate() {
+            for (x, v) in row.iter().enumerate() {
+              if *v != 0 {
+                bg[(ty * 8 + y) * width + tx * 8 + x] = (pal[sub_palette[*v as usize - 1]], true);
+              }
+            }
+          }
+        }
       }
+    }
+
+    // sprite layer: colour, and whether the sprite is behind the background
+    // data are drawn from last to first, so that the first opaque pixel wins like in OAM
+    let mut sprites: Vec<Option<([u8; 3], bool)>> = vec![None; width * height];
+    for d in self.data.iter().rev() {
+      let chr = Self::get_data_chr(chrs, d)?;
+      let colors = self.get_data_colors(d, &chr, pal)?;
 
-      append_pattern_on_image(
-        &mut img,
-        chr,
-        d.x,
-        d.y,
-        ChrPalette {
-          cbg: d.cbg.map(|c| pal[c]),
-          c0: pal[d.c0],
-          c1: pal[d.c1],
-          c2: pal[d.c2],
-        },
-      );
+      for (y, row) in chr.iter().enumerate() {
+        for (x, v) in row.iter().enumerate() {
+          if *v != 0 {
+            sprites[(d.y as usize + y) * width + d.x as usize + x] =
+              Some((colors[*v as usize - 1], d.behind_background));
+          }
+        }
+      }
     }
 
-    Ok(img)
+    Ok(RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+      let i = y as usize * width + x as usize;
+      let (bg_color, bg_opaque) = bg[i];
+      let c = match sprites[i] {
+        Some((c, behind)) if !(behind && bg_opaque) => c,
+        _ => bg_color,
+      };
+      Rgba([c[0], c[1], c[2], 255])
+    }))
   }
 
   /// renders the atlas to a file
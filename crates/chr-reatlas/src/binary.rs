@@ -0,0 +1,76 @@
+use std::{
+  fs::File,
+  io::{Cursor, Read, Seek, SeekFrom},
+};
+
+use zip::ZipArchive;
+
+/// splits a binary path pointing into a zip archive into the archive path and the entry name
+///
+/// `archive.zip` gives no entry name, `archive.zip#inner.nes` gives `inner.nes` \
+/// returns `None` if the path does not point into a zip archive
+pub fn split_archive_path(path: &str) -> Option<(&str, Option<&str>)> {
+  let is_zip = |p: &str| p.to_lowercase().ends_with(".zip");
+
+  match path.rsplit_once('#') {
+    Some((archive, entry)) if is_zip(archive) => Some((archive, Some(entry))),
+    _ if is_zip(path) => Some((path, None)),
+    _ => None,
+  }
+}
+
+/// reads a whole entry of a zip archive, the first file of the archive if `entry` is `None`
+fn read_archive_entry(archive: &str, entry: Option<&str>) -> Result<Vec<u8>, crate::Error> {
+  let file = File::open(archive).map_err(crate::Error::IOError)?;
+  let mut zip = ZipArchive::new(file).map_err(crate::Error::ZipError)?;
+
+  let mut file = match entry {
+    Some(name) => zip.by_name(name).map_err(crate::Error::ZipError)?,
+    None => {
+      let index = (0..zip.len())
+        .find(|i| zip.name_for_index(*i).is_some_and(|n| !n.ends_with('/')))
+        .ok_or(crate::Error::ZipError(zip::result::ZipError::FileNotFound))?;
+      zip.by_index(index).map_err(crate::Error::ZipError)?
+    }
+  };
+
+  let mut buf = Vec::new();
+  file.read_to_end(&mut buf).map_err(crate::Error::IOError)?;
+  Ok(buf)
+}
+
+/// reads a whole binary
+///
+/// the path can point into a zip archive, see [`split_archive_path`]
+pub fn read_binary(path: &str) -> Result<Vec<u8>, crate::Error> {
+  match split_archive_path(path) {
+    Some((archive, entry)) => read_archive_entry(archive, entry),
+    None => std::fs::read(path).map_err(crate::Error::IOError),
+  }
+}
+
+/// reads `length` bytes of a binary, starting at `start`
+///
+/// the path can point into a zip archive, see [`split_archive_path`]
+pub fn read_region(path: &str, start: u64, length: usize) -> Result<Vec<u8>, crate::Error> {
+  let mut buf = vec![0; length];
+
+  match split_archive_path(path) {
+    Some((archive, entry)) => {
+      let mut cursor = Cursor::new(read_archive_entry(archive, entry)?);
+      cursor
+        .seek(SeekFrom::Start(start))
+        .map_err(crate::Error::IOError)?;
+      cursor.read_exact(&mut buf).map_err(crate::Error::IOError)?;
+    }
+    None => {
+      let mut file = File::open(path).map_err(crate::Error::IOError)?;
+      file
+        .seek(SeekFrom::Start(start))
+        .map_err(crate::Error::IOError)?;
+      file.read_exact(&mut buf).map_err(crate::Error::IOError)?;
+    }
+  }
+
+  Ok(buf)
+}
@@ -1,6 +1,8 @@
 use arbitrary_int::u2;
 use bitvec::{order::Msb0, view::BitView};
 
+use crate::codec::TilePattern;
+
 pub type ChrPixelPattern = [[u2; 8]; 8];
 
 // --- READING ---
@@ -31,19 +33,19 @@ pub fn read_single_chr(b: [u8; 16]) -> ChrPixelPattern {
 }
 
 /// x flip a chr
-pub fn flip_x(b: &mut ChrPixelPattern) {
+pub fn flip_x<T>(b: &mut [[T; 8]; 8]) {
   for bb in b {
     bb.reverse();
   }
 }
 
 /// y flip a chr
-pub fn flip_y(b: &mut ChrPixelPattern) {
+pub fn flip_y<T>(b: &mut [[T; 8]; 8]) {
   b.reverse();
 }
 
 /// transpose a chr
-pub fn transpose(b: &mut ChrPixelPattern) {
+pub fn transpose<T: Copy>(b: &mut [[T; 8]; 8]) {
   for i in 1..8 {
     for j in 0..i {
       let o = b[i][j];
@@ -53,6 +55,11 @@ pub fn transpose(b: &mut ChrPixelPattern) {
   }
 }
 
+/// converts a chr to a tile pattern
+pub fn to_tile_pattern(b: ChrPixelPattern) -> TilePattern {
+  b.map(|row| row.map(|v| v.value()))
+}
+
 /// read a vector of chrs from bytes
 pub fn read_bytes(b: Vec<u8>) -> Result<Vec<ChrPixelPattern>, crate::Error> {
   let slices = b.chunks(16);
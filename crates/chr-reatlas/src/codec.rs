@@ -0,0 +1,208 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// an 8x8 tile, decoded to pixel values
+///
+/// pixel value 0 is the background/transparent colour
+pub type TilePattern = [[u8; 8]; 8];
+
+/// decodes and encodes the tiles of a graphics format
+pub trait TileCodec {
+  /// bits per pixel, tiles have `2^bits_per_pixel` colours
+  fn bits_per_pixel(&self) -> usize;
+
+  /// size of a tile, in bytes
+  fn bytes_per_tile(&self) -> usize {
+    self.bits_per_pixel() * 8
+  }
+
+  /// decodes a tile from its bytes
+  ///
+  /// `b` must be [`TileCodec::bytes_per_tile`] long
+  fn decode(&self, b: &[u8]) -> TilePattern;
+
+  /// encodes a tile to its bytes
+  fn encode(&self, t: &TilePattern) -> Vec<u8>;
+}
+
+/// a format where each row of a tile is stored as one byte per bitplane
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PlanarCodec {
+  /// offset of the first row of each plane, lowest bit first
+  pub plane_offsets: Vec<usize>,
+  /// distance between two rows of a same plane, in bytes
+  pub row_stride: usize,
+}
+
+impl TileCodec for PlanarCodec {
+  fn bits_per_pixel(&self) -> usize {
+    self.plane_offsets.len()
+  }
+
+  fn bytes_per_tile(&self) -> usize {
+    self.plane_offsets.iter().max().unwrap_or(&0) + 7 * self.row_stride + 1
+  }
+
+  fn decode(&self, b: &[u8]) -> TilePattern {
+    let mut r = [[0; 8]; 8];
+
+    for (bit, offset) in self.plane_offsets.iter().enumerate() {
+      for (y, row) in r.iter_mut().enumerate() {
+        let plane = b[offset + y * self.row_stride];
+        for (x, v) in row.iter_mut().enumerate() {
+          *v |= ((plane >> (7 - x)) & 1) << bit;
+        }
+      }
+    }
+
+    r
+  }
+
+  fn encode(&self, t: &TilePattern) -> Vec<u8> {
+    let mut b = vec![0; self.bytes_per_tile()];
+
+    for (bit, offset) in self.plane_offsets.iter().enumerate() {
+      for (y, row) in t.iter().enumerate() {
+        b[offset + y * self.row_stride] = row
+          .iter()
+          .enumerate()
+          .fold(0, |acc, (x, v)| acc | ((v >> bit) & 1) << (7 - x));
+      }
+    }
+
+    b
+  }
+}
+
+/// a format where pixels are packed in bytes, leftmost pixel in the highest bits
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PackedCodec {
+  /// bits per pixel, must divide 8
+  pub bits_per_pixel: usize,
+}
+
+impl TileCodec for PackedCodec {
+  fn bits_per_pixel(&self) -> usize {
+    self.bits_per_pixel
+  }
+
+  fn decode(&self, b: &[u8]) -> TilePattern {
+    let mut r = [[0; 8]; 8];
+    let pixels_per_byte = 8 / self.bits_per_pixel;
+    let mask = ((1u16 << self.bits_per_pixel) - 1) as u8;
+
+    for (y, row) in r.iter_mut().enumerate() {
+      for (x, v) in row.iter_mut().enumerate() {
+        let byte = b[(y * 8 + x) / pixels_per_byte];
+        let shift = 8 - self.bits_per_pixel * (x % pixels_per_byte + 1);
+        *v = (byte >> shift) & mask;
+      }
+    }
+
+    r
+  }
+
+  fn encode(&self, t: &TilePattern) -> Vec<u8> {
+    let mut b = vec![0; self.bytes_per_tile()];
+    let pixels_per_byte = 8 / self.bits_per_pixel;
+
+    for (y, row) in t.iter().enumerate() {
+      for (x, v) in row.iter().enumerate() {
+        let shift = 8 - self.bits_per_pixel * (x % pixels_per_byte + 1);
+        b[(y * 8 + x) / pixels_per_byte] |= v << shift;
+      }
+    }
+
+    b
+  }
+}
+
+/// tile formats known by the library
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum TileFormat {
+  /// NES/Famicom 2bpp, 8 bytes of plane 0 followed by 8 bytes of plane 1
+  #[default]
+  #[cfg_attr(feature = "serde", serde(rename = "nes"))]
+  Nes,
+  /// 1bpp, one byte per row, used by many fonts
+  #[cfg_attr(feature = "serde", serde(rename = "1bpp"))]
+  OneBpp,
+  /// Game Boy (and SNES) 2bpp, planes 0 and 1 interleaved on each row
+  #[cfg_attr(feature = "serde", serde(rename = "gb", alias = "snes_2bpp"))]
+  GameBoy,
+  /// SNES 4bpp, two interleaved 2bpp halves (planes 0/1, then planes 2/3)
+  #[cfg_attr(feature = "serde", serde(rename = "snes_4bpp"))]
+  Snes4bpp,
+  /// SNES 8bpp, four interleaved 2bpp quarters
+  #[cfg_attr(feature = "serde", serde(rename = "snes_8bpp"))]
+  Snes8bpp,
+  /// Master System / Game Gear 4bpp, four interleaved planes on each row
+  #[cfg_attr(feature = "serde", serde(rename = "sms", alias = "gg"))]
+  MasterSystem,
+  /// Genesis / Mega Drive 4bpp, packed nibbles, leftmost pixel in the high nibble
+  #[cfg_attr(feature = "serde", serde(rename = "genesis", alias = "md"))]
+  Genesis,
+}
+
+impl TileFormat {
+  /// every tile format, with its name
+  pub const ALL: &[(&str, TileFormat)] = &[
+    ("nes", TileFormat::Nes),
+    ("1bpp", TileFormat::OneBpp),
+    ("gb", TileFormat::GameBoy),
+    ("snes_2bpp", TileFormat::GameBoy),
+    ("snes_4bpp", TileFormat::Snes4bpp),
+    ("snes_8bpp", TileFormat::Snes8bpp),
+    ("sms", TileFormat::MasterSystem),
+    ("gg", TileFormat::MasterSystem),
+    ("genesis", TileFormat::Genesis),
+    ("md", TileFormat::Genesis),
+  ];
+
+  /// returns the codec of the format
+  pub fn codec(&self) -> Box<dyn TileCodec> {
+    let (plane_offsets, row_stride) = match self {
+      TileFormat::Nes => (vec![0, 8], 1),
+      TileFormat::OneBpp => (vec![0], 1),
+      TileFormat::GameBoy => (vec![0, 1], 2),
+      TileFormat::Snes4bpp => (vec![0, 1, 16, 17], 2),
+      TileFormat::Snes8bpp => (vec![0, 1, 16, 17, 32, 33, 48, 49], 2),
+      TileFormat::MasterSystem => (vec![0, 1, 2, 3], 4),
+      TileFormat::Genesis => return Box::new(PackedCodec { bits_per_pixel: 4 }),
+    };
+
+    Box::new(PlanarCodec {
+      plane_offsets,
+      row_stride,
+    })
+  }
+}
+
+impl std::str::FromStr for TileFormat {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    TileFormat::ALL
+      .iter()
+      .find(|(name, _)| *name == s)
+      .map(|(_, f)| *f)
+      .ok_or(crate::Error::TileFormatParseError(s.to_string()))
+  }
+}
+
+/// decodes a vector of tiles from bytes
+pub fn read_tiles(b: &[u8], codec: &dyn TileCodec) -> Result<Vec<TilePattern>, crate::Error> {
+  let size = codec.bytes_per_tile();
+  if !b.len().is_multiple_of(size) {
+    return Err(crate::Error::TileSizeError(b.len(), size));
+  }
+
+  Ok(b.chunks(size).map(|c| codec.decode(c)).collect())
+}
+
+/// encodes a vector of tiles to bytes
+pub fn write_tiles(tiles: &[TilePattern], codec: &dyn TileCodec) -> Vec<u8> {
+  tiles.iter().flat_map(|t| codec.encode(t)).collect()
+}
@@ -0,0 +1,207 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// compression schemes graphics can be stored with
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Compression {
+  /// Shiru's RLE (NES Screen Tool, neslib's `vram_unrle`)
+  ///
+  /// the first byte is a tag, any other byte is a literal \
+  /// the tag followed by `n` repeats the previous byte `n` times, or ends the stream if `n` is 0
+  Rle,
+  /// Konami RLE
+  ///
+  /// `$00-$7F` repeats the next byte `n` times, `$80-$FE` copies `n - $80` literal bytes, `$FF`
+  /// ends the stream
+  KonamiRle,
+  /// Okumura's LZSS, 4096 byte window filled with spaces, flags read lowest bit first
+  ///
+  /// the stream has no end marker, so decoding stops at the wanted decompressed size
+  Lzss,
+  /// LZ10 (GBA / DS BIOS LZ77), 4 byte header with the decompressed size
+  Lz10,
+}
+
+/// output of a decompression
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Decompressed {
+  /// decompressed bytes
+  pub data: Vec<u8>,
+  /// size of the compressed stream that was read, in bytes
+  pub consumed: usize,
+}
+
+/// reads input bytes, keeping track of how many were consumed
+struct Input<'a> {
+  b: &'a [u8],
+  position: usize,
+}
+
+impl Input<'_> {
+  fn next(&mut self) -> Result<u8, crate::Error> {
+    let v = *self
+      .b
+      .get(self.position)
+      .ok_or(crate::Error::DecompressionError(self.position))?;
+    self.position += 1;
+    Ok(v)
+  }
+}
+
+impl Compression {
+  /// every compression scheme, with its name
+  pub const ALL: &[(&str, Compression)] = &[
+    ("rle", Compression::Rle),
+    ("konami_rle", Compression::KonamiRle),
+    ("lzss", Compression::Lzss),
+    ("lz10", Compression::Lz10),
+  ];
+
+  /// decompresses a stream starting at the first byte of `b`
+  ///
+  /// `size` is the decompressed size wanted, schemes without an end marker stop there, other
+  /// schemes read their whole stream
+  pub fn decompress(&self, b: &[u8], size: usize) -> Result<Decompressed, crate::Error> {
+    let mut input = Input { b, position: 0 };
+    let data = match self {
+      Compression::Rle => decompress_rle(&mut input)?,
+      Compression::KonamiRle => decompress_konami_rle(&mut input)?,
+      Compression::Lzss => decompress_lzss(&mut input, size)?,
+      Compression::Lz10 => decompress_lz10(&mut input)?,
+    };
+
+    Ok(Decompressed {
+      data,
+      consumed: input.position,
+    })
+  }
+}
+
+impl std::str::FromStr for Compression {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Compression::ALL
+      .iter()
+      .find(|(name, _)| *name == s)
+      .map(|(_, c)| *c)
+      .ok_or(crate::Error::CompressionParseError(s.to_string()))
+  }
+}
+
+fn decompress_rle(input: &mut Input) -> Result<Vec<u8>, crate::Error> {
+  let mut out = Vec::new();
+  let tag = input.next()?;
+  let mut last = 0;
+
+  loop {
+    let v = input.next()?;
+    if v != tag {
+      out.push(v);
+      last = v;
+      continue;
+    }
+
+    match input.next()? {
+      0 => break,
+      n => out.extend(std::iter::repeat_n(last, n as usize)),
+    }
+  }
+
+  Ok(out)
+}
+
+fn decompress_konami_rle(input: &mut Input) -> Result<Vec<u8>, crate::Error> {
+  let mut out = Vec::new();
+
+  loop {
+    match input.next()? {
+      0xFF => break,
+      n @ 0x00..=0x7F => {
+        let v = input.next()?;
+        out.extend(std::iter::repeat_n(v, n as usize));
+      }
+      n => {
+        for _ in 0..n - 0x80 {
+          out.push(input.next()?);
+        }
+      }
+    }
+  }
+
+  Ok(out)
+}
+
+fn decompress_lzss(input: &mut Input, size: usize) -> Result<Vec<u8>, crate::Error> {
+  const WINDOW: usize = 4096;
+  const MAX_LENGTH: usize = 18;
+
+  let mut out = Vec::new();
+  let mut window = [b' '; WINDOW];
+  let mut r = WINDOW - MAX_LENGTH;
+
+  while out.len() < size {
+    let flags = input.next()?;
+    for bit in 0..8 {
+      if out.len() >= size {
+        break;
+      }
+
+      if (flags >> bit) & 1 == 1 {
+        let v = input.next()?;
+        out.push(v);
+        window[r] = v;
+        r = (r + 1) % WINDOW;
+      } else {
+        let (b0, b1) = (input.next()? as usize, input.next()? as usize);
+        let offset = b0 | ((b1 & 0xF0) << 4);
+        let length = (b1 & 0x0F) + 3;
+        for k in 0..length {
+          let v = window[(offset + k) % WINDOW];
+          out.push(v);
+          window[r] = v;
+          r = (r + 1) % WINDOW;
+        }
+      }
+    }
+  }
+
+  Ok(out)
+}
+
+fn decompress_lz10(input: &mut Input) -> Result<Vec<u8>, crate::Error> {
+  if input.next()? != 0x10 {
+    return Err(crate::Error::DecompressionError(0));
+  }
+  let size = u32::from_le_bytes([input.next()?, input.next()?, input.next()?, 0]) as usize;
+
+  let mut out = Vec::with_capacity(size);
+  while out.len() < size {
+    let flags = input.next()?;
+    for bit in (0..8).rev() {
+      if out.len() >= size {
+        break;
+      }
+
+      if (flags >> bit) & 1 == 0 {
+        out.push(input.next()?);
+      } else {
+        let position = input.position;
+        let (b0, b1) = (input.next()? as usize, input.next()? as usize);
+        let length = (b0 >> 4) + 3;
+        let distance = (((b0 & 0x0F) << 8) | b1) + 1;
+        if distance > out.len() {
+          return Err(crate::Error::DecompressionError(position));
+        }
+        for _ in 0..length {
+          out.push(out[out.len() - distance]);
+        }
+      }
+    }
+  }
+  out.truncate(size);
+
+  Ok(out)
+}
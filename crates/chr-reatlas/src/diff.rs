@@ -0,0 +1,149 @@
+use image::{Rgba, RgbaImage};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::{
+  codec::{TileFormat, TilePattern, read_tiles},
+  pal::TilePalette,
+  render::{get_tiles_with_graduations, graduated_tile_position},
+};
+
+/// a tile that differs between two chr regions
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TileDiff {
+  /// index of the tile in the regions
+  pub index: usize,
+  /// address of the tile, relative to the start of the regions
+  pub offset: usize,
+  /// number of differing pixels, `None` if the tile is only in one of the regions
+  pub pixels: Option<usize>,
+  /// number of differing bytes
+  pub bytes: usize,
+}
+
+/// tile by tile comparison of two chr regions
+#[derive(Clone, Debug)]
+pub struct ChrDiff {
+  /// format of the tiles
+  pub format: TileFormat,
+  /// tiles of the first region
+  pub a: Vec<TilePattern>,
+  /// tiles of the second region
+  pub b: Vec<TilePattern>,
+  /// differing tiles, by index
+  pub changed: Vec<TileDiff>,
+}
+
+/// colour of the changed pixels in the overlay
+const CHANGED_COLOR: [u8; 3] = [255, 48, 48];
+/// space between the sheets of a diff render, in pixels
+const SHEET_GAP: u32 = 8;
+
+/// compares two chr regions tile by tile
+///
+/// regions of different sizes are compared up to the end of the longest one, tiles past the end
+/// of the other region count as changed
+pub fn diff_chr(a: &[u8], b: &[u8], format: TileFormat) -> Result<ChrDiff, crate::Error> {
+  let codec = format.codec();
+  let size = codec.bytes_per_tile();
+  let tiles_a = read_tiles(a, &*codec)?;
+  let tiles_b = read_tiles(b, &*codec)?;
+
+  let mut changed = Vec::new();
+  for index in 0..tiles_a.len().max(tiles_b.len()) {
+    let offset = index * size;
+    let diff = match (tiles_a.get(index), tiles_b.get(index)) {
+      (Some(ta), Some(tb)) => {
+        let pixels = ta
+          .iter()
+          .flatten()
+          .zip(tb.iter().flatten())
+          .filter(|(pa, pb)| pa != pb)
+          .count();
+        let bytes = a[offset..offset + size]
+          .iter()
+          .zip(b[offset..offset + size].iter())
+          .filter(|(ba, bb)| ba != bb)
+          .count();
+        (pixels > 0).then_some(TileDiff {
+          index,
+          offset,
+          pixels: Some(pixels),
+          bytes,
+        })
+      }
+      _ => Some(TileDiff {
+        index,
+        offset,
+        pixels: None,
+        bytes: size,
+      }),
+    };
+    changed.extend(diff);
+  }
+
+  Ok(ChrDiff {
+    format,
+    a: tiles_a,
+    b: tiles_b,
+    changed,
+  })
+}
+
+impl ChrDiff {
+  /// draws the two regions and an overlay side by side on a black background, graduated in
+  /// hexadecimal
+  ///
+  /// the overlay shows the second region in dark greys, with the changed pixels highlighted
+  pub fn get_image(&self) -> Result<RgbaImage, crate::Error> {
+    let bits_per_pixel = self.format.codec().bits_per_pixel();
+    let greys = TilePalette::greyscale(bits_per_pixel);
+    let dark = TilePalette {
+      cbg: None,
+      colors: greys.colors.iter().map(|c| c.map(|v| v / 3)).collect(),
+    };
+
+    let sheet_a = get_tiles_with_graduations(&self.a, &vec![greys.clone(); self.a.len()])?;
+    let sheet_b = get_tiles_with_graduations(&self.b, &vec![greys.clone(); self.b.len()])?;
+    let mut overlay = get_tiles_with_graduations(&self.b, &vec![dark; self.b.len()])?;
+
+    // tiles only in the first region are not in the overlay, tiles only in the second are all red
+    let blank = [[0; 8]; 8];
+    for d in self.changed.iter().filter(|d| d.index < self.b.len()) {
+      let ta = self.a.get(d.index).unwrap_or(&blank);
+      let tb = &self.b[d.index];
+      let (sx, sy) = graduated_tile_position(d.index);
+      for (y, (ra, rb)) in ta.iter().zip(tb.iter()).enumerate() {
+        for (x, (pa, pb)) in ra.iter().zip(rb.iter()).enumerate() {
+          if pa != pb || d.pixels.is_none() {
+            let c = CHANGED_COLOR;
+            overlay.put_pixel(sx + x as u32, sy + y as u32, Rgba([c[0], c[1], c[2], 255]));
+          }
+        }
+      }
+    }
+
+    let sheets = [sheet_a, sheet_b, overlay];
+    let width = sheets.iter().map(|s| s.width()).sum::<u32>() + SHEET_GAP * 2;
+    let height = sheets.iter().map(|s| s.height()).max().unwrap_or(0);
+    let mut img = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    let mut x = 0;
+    for sheet in &sheets {
+      image::imageops::overlay(&mut img, sheet, x as i64, 0);
+      x += sheet.width() + SHEET_GAP;
+    }
+
+    Ok(img)
+  }
+
+  /// renders the two regions and an overlay side by side, see [`ChrDiff::get_image`]
+  pub fn render(&self, path: String) -> Result<(), crate::Error> {
+    self
+      .get_image()?
+      .save(path)
+      .map_err(crate::Error::ImageError)?;
+
+    Ok(())
+  }
+}
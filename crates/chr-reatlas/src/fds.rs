@@ -0,0 +1,173 @@
+/// size of a disk side in a .fds image, in bytes
+pub const FDS_SIDE_SIZE: usize = 65500;
+/// size of the optional fwNES header of a .fds image, in bytes
+pub const FDS_HEADER_SIZE: usize = 16;
+
+const DISK_INFO_BLOCK: u8 = 0x01;
+const FILE_AMOUNT_BLOCK: u8 = 0x02;
+const FILE_HEADER_BLOCK: u8 = 0x03;
+const FILE_DATA_BLOCK: u8 = 0x04;
+
+const DISK_INFO_SIZE: usize = 56;
+const FILE_AMOUNT_SIZE: usize = 2;
+const FILE_HEADER_SIZE: usize = 16;
+
+/// a Famicom Disk System image
+#[derive(Clone, Debug)]
+pub struct FdsImage {
+  /// disk sides, side A of the first disk first
+  pub sides: Vec<FdsSide>,
+}
+
+/// a side of a disk
+#[derive(Clone, Debug)]
+pub struct FdsSide {
+  /// game name, from the disk info block
+  pub game_name: String,
+  /// number of files given by the file amount block
+  ///
+  /// files past this amount are hidden from the BIOS, but are still listed in [`FdsSide::files`]
+  pub file_amount: usize,
+  /// files of the side, in disk order
+  pub files: Vec<FdsFile>,
+}
+
+/// a file of a disk side
+#[derive(Clone, Debug)]
+pub struct FdsFile {
+  /// file number
+  pub number: u8,
+  /// file id, used by the BIOS to select files to load
+  pub id: u8,
+  /// file name, 8 characters
+  pub name: String,
+  /// address the file is loaded to, in cpu or ppu memory depending on [`FdsFile::kind`]
+  pub load_address: u16,
+  /// kind of the file
+  pub kind: FdsFileKind,
+  /// offset of the file data in the image
+  pub offset: usize,
+  /// file data
+  pub data: Vec<u8>,
+}
+
+/// kind of a file, tells the BIOS where to load it
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum FdsFileKind {
+  /// program data, loaded in cpu memory
+  Prg,
+  /// chr data, loaded in the pattern tables
+  Chr,
+  /// nametable data, loaded in ppu memory
+  Nametable,
+  /// unknown kind
+  Unknown(u8),
+}
+
+impl From<u8> for FdsFileKind {
+  fn from(value: u8) -> Self {
+    match value {
+      0 => FdsFileKind::Prg,
+      1 => FdsFileKind::Chr,
+      2 => FdsFileKind::Nametable,
+      v => FdsFileKind::Unknown(v),
+    }
+  }
+}
+
+impl std::fmt::Display for FdsFileKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FdsFileKind::Prg => write!(f, "PRG"),
+      FdsFileKind::Chr => write!(f, "CHR"),
+      FdsFileKind::Nametable => write!(f, "NT"),
+      FdsFileKind::Unknown(v) => write!(f, "?{:02X}", v),
+    }
+  }
+}
+
+/// whether bytes look like a .fds image, with or without its fwNES header
+pub fn is_fds_image(b: &[u8]) -> bool {
+  b.starts_with(b"FDS\x1A") || b.starts_with(b"\x01*NINTENDO-HVC*")
+}
+
+/// returns the block of `size` bytes at `offset`, checking its block code
+fn block(b: &[u8], offset: usize, code: u8, size: usize) -> Result<&[u8], crate::Error> {
+  match b.get(offset..offset + size) {
+    Some(block) if block[0] == code => Ok(block),
+    _ => Err(crate::Error::FdsBlockError(offset, code)),
+  }
+}
+
+impl FdsImage {
+  /// reads a .fds image from its bytes
+  pub fn from_bytes(b: &[u8]) -> Result<Self, crate::Error> {
+    let header_size = match b.starts_with(b"FDS\x1A") {
+      true => FDS_HEADER_SIZE.min(b.len()),
+      false => 0,
+    };
+
+    let mut sides = Vec::new();
+    for (s, side) in b[header_size..].chunks(FDS_SIDE_SIZE).enumerate() {
+      let side_offset = header_size + s * FDS_SIDE_SIZE;
+      sides.push(FdsSide::from_bytes(side, side_offset).map_err(|e| match e {
+        crate::Error::FdsBlockError(offset, code) => {
+          crate::Error::FdsBlockError(side_offset + offset, code)
+        }
+        e => e,
+      })?);
+    }
+
+    Ok(Self { sides })
+  }
+
+  /// returns a file by side and index in the side
+  pub fn file(&self, side: usize, file: usize) -> Result<&FdsFile, crate::Error> {
+    self
+      .sides
+      .get(side)
+      .and_then(|s| s.files.get(file))
+      .ok_or(crate::Error::FdsFileError(side, file))
+  }
+}
+
+impl FdsSide {
+  /// reads a side from its bytes, `image_offset` being the offset of the side in the image
+  fn from_bytes(b: &[u8], image_offset: usize) -> Result<Self, crate::Error> {
+    let info = block(b, 0, DISK_INFO_BLOCK, DISK_INFO_SIZE)?;
+    if &info[1..15] != b"*NINTENDO-HVC*" {
+      return Err(crate::Error::FdsBlockError(0, DISK_INFO_BLOCK));
+    }
+    let game_name = String::from_utf8_lossy(&info[16..19]).to_string();
+
+    let amount = block(b, DISK_INFO_SIZE, FILE_AMOUNT_BLOCK, FILE_AMOUNT_SIZE)?;
+    let file_amount = amount[1] as usize;
+
+    // files are read until the next block is not a file header, which includes hidden files
+    let mut files = Vec::new();
+    let mut offset = DISK_INFO_SIZE + FILE_AMOUNT_SIZE;
+    while b.get(offset) == Some(&FILE_HEADER_BLOCK) {
+      let header = block(b, offset, FILE_HEADER_BLOCK, FILE_HEADER_SIZE)?;
+      let size = u16::from_le_bytes([header[13], header[14]]) as usize;
+      offset += FILE_HEADER_SIZE;
+
+      let data = block(b, offset, FILE_DATA_BLOCK, size + 1)?;
+      files.push(FdsFile {
+        number: header[1],
+        id: header[2],
+        name: String::from_utf8_lossy(&header[3..11]).to_string(),
+        load_address: u16::from_le_bytes([header[11], header[12]]),
+        kind: FdsFileKind::from(header[15]),
+        offset: image_offset + offset + 1,
+        data: data[1..].to_vec(),
+      });
+      offset += size + 1;
+    }
+
+    Ok(Self {
+      game_name,
+      file_amount,
+      files,
+    })
+  }
+}
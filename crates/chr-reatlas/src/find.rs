@@ -0,0 +1,206 @@
+use image::RgbaImage;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::{
+  chr::{flip_x, flip_y, transpose},
+  codec::{TileFormat, TilePattern},
+};
+
+/// transposition and flips of a tile, applied in that order like in atlas data
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct TileTransform {
+  /// transpose
+  pub transpose: bool,
+  /// flip x
+  pub flip_x: bool,
+  /// flip y
+  pub flip_y: bool,
+}
+
+impl TileTransform {
+  /// every combination of transposition and flips, the identity first
+  pub const ALL: [TileTransform; 8] = [
+    TileTransform::new(false, false, false),
+    TileTransform::new(false, true, false),
+    TileTransform::new(false, false, true),
+    TileTransform::new(false, true, true),
+    TileTransform::new(true, false, false),
+    TileTransform::new(true, true, false),
+    TileTransform::new(true, false, true),
+    TileTransform::new(true, true, true),
+  ];
+
+  const fn new(transpose: bool, flip_x: bool, flip_y: bool) -> Self {
+    Self {
+      transpose,
+      flip_x,
+      flip_y,
+    }
+  }
+
+  /// applies the transform to a tile
+  pub fn apply<T: Copy>(&self, t: &mut [[T; 8]; 8]) {
+    if self.transpose {
+      transpose(t);
+    }
+    if self.flip_x {
+      flip_x(t);
+    }
+    if self.flip_y {
+      flip_y(t);
+    }
+  }
+
+  /// undoes the transform on a tile
+  pub fn apply_inverse<T: Copy>(&self, t: &mut [[T; 8]; 8]) {
+    if self.flip_y {
+      flip_y(t);
+    }
+    if self.flip_x {
+      flip_x(t);
+    }
+    if self.transpose {
+      transpose(t);
+    }
+  }
+}
+
+/// a tile of a binary matching a cell of an image
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TileMatch {
+  /// x position of the cell in the image, in pixels
+  pub x: u32,
+  /// y position of the cell in the image, in pixels
+  pub y: u32,
+  /// address of the tile in the binary
+  pub offset: usize,
+  /// index of the tile, counted in tiles from the start of the binary
+  pub tile_index: usize,
+  /// transform to apply to the tile to get the cell
+  pub transform: TileTransform,
+}
+
+/// options of the tile finder
+#[derive(Clone, Copy, Debug)]
+pub struct FindOptions {
+  /// format of the tiles of the binary
+  pub format: TileFormat,
+  /// distance between two searched addresses, the size of a tile if `None`
+  pub alignment: Option<usize>,
+}
+
+impl Default for FindOptions {
+  fn default() -> Self {
+    Self {
+      format: TileFormat::Nes,
+      alignment: None,
+    }
+  }
+}
+
+/// renumbers the values of a tile by order of first appearance, which makes it colour-agnostic
+pub fn normalize_pattern<T: Copy + Eq>(t: &[[T; 8]; 8]) -> TilePattern {
+  let mut seen: Vec<T> = Vec::new();
+  t.map(|row| {
+    row.map(|v| match seen.iter().position(|s| *s == v) {
+      Some(p) => p as u8,
+      None => {
+        seen.push(v);
+        (seen.len() - 1) as u8
+      }
+    })
+  })
+}
+
+/// tiles indexed by normalized shape, under every transform
+#[derive(Clone, Debug, Default)]
+pub struct TileIndex {
+  shapes: HashMap<TilePattern, (usize, TileTransform)>,
+}
+
+impl TileIndex {
+  /// indexes tiles, the lowest index and the simplest transform are kept for identical shapes
+  pub fn new(tiles: &[TilePattern]) -> Self {
+    let mut shapes = HashMap::new();
+    for (i, tile) in tiles.iter().enumerate() {
+      for transform in TileTransform::ALL {
+        let mut t = *tile;
+        transform.apply(&mut t);
+        shapes
+          .entry(normalize_pattern(&t))
+          .or_insert((i, transform));
+      }
+    }
+    Self { shapes }
+  }
+
+  /// returns the index of a tile with a normalized shape, and the transform that gives the shape
+  pub fn get(&self, shape: &TilePattern) -> Option<(usize, TileTransform)> {
+    self.shapes.get(shape).copied()
+  }
+}
+
+/// returns the 8x8 cell of an image at a pixel position
+pub fn image_cell(image: &RgbaImage, x: u32, y: u32) -> [[[u8; 4]; 8]; 8] {
+  let mut cell = [[[0; 4]; 8]; 8];
+  for (cy, row) in cell.iter_mut().enumerate() {
+    for (cx, p) in row.iter_mut().enumerate() {
+      *p = image.get_pixel(x + cx as u32, y + cy as u32).0;
+    }
+  }
+  cell
+}
+
+/// finds the tiles of a binary that produce the 8x8 cells of an image
+///
+/// cells are compared by shape only, so any palette matches \
+/// cells of a single colour or with more colours than the format allows are skipped, as well as
+/// the partial cells on the right and bottom edges
+pub fn find_tiles(image: &RgbaImage, b: &[u8], options: &FindOptions) -> Vec<TileMatch> {
+  let codec = options.format.codec();
+  let size = codec.bytes_per_tile();
+  let colors = 1 << codec.bits_per_pixel();
+
+  // normalized tile a cell comes from, for each transform
+  let mut wanted: HashMap<TilePattern, Vec<(u32, u32, TileTransform)>> = HashMap::new();
+  for y in (0..image.height() / 8).map(|y| y * 8) {
+    for x in (0..image.width() / 8).map(|x| x * 8) {
+      let cell = normalize_pattern(&image_cell(image, x, y));
+      let used = cell.iter().flatten().max().copied().unwrap_or(0) as usize + 1;
+      if used == 1 || used > colors {
+        continue;
+      }
+
+      for transform in TileTransform::ALL {
+        let mut t = cell;
+        transform.apply_inverse(&mut t);
+        let entry = wanted.entry(normalize_pattern(&t)).or_default();
+        if !entry.iter().any(|(ex, ey, _)| *ex == x && *ey == y) {
+          entry.push((x, y, transform));
+        }
+      }
+    }
+  }
+
+  let mut matches = Vec::new();
+  let alignment = options.alignment.unwrap_or(size).max(1);
+  for offset in (0..b.len().saturating_sub(size - 1)).step_by(alignment) {
+    let tile = normalize_pattern(&codec.decode(&b[offset..offset + size]));
+    for (x, y, transform) in wanted.get(&tile).into_iter().flatten() {
+      matches.push(TileMatch {
+        x: *x,
+        y: *y,
+        offset,
+        tile_index: offset / size,
+        transform: *transform,
+      });
+    }
+  }
+
+  matches.sort_by_key(|m| (m.y, m.x, m.offset));
+  matches
+}
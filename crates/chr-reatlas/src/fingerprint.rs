@@ -0,0 +1,412 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+  codec::{TileFormat, TilePattern},
+  find::{TileTransform, normalize_pattern},
+};
+
+/// fingerprint of a tile, identical for tiles that only differ by their colours
+///
+/// with transforms, tiles that are flips or transpositions of each other also share their
+/// fingerprint
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(into = "String"))]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Debug)]
+pub struct TileFingerprint {
+  /// canonical form of the tile, values renumbered by order of first appearance
+  pub pattern: TilePattern,
+}
+
+impl TileFingerprint {
+  /// fingerprints a tile, like a [`TilePattern`] or a [`ChrPixelPattern`](crate::chr::ChrPixelPattern)
+  ///
+  /// with `transforms`, the canonical form is the smallest one among every flip and transposition
+  pub fn new<T: Copy + Eq>(t: &[[T; 8]; 8], transforms: bool) -> Self {
+    let pattern = normalize_pattern(t);
+    let pattern = match transforms {
+      true => variants(&pattern, true)
+        .into_iter()
+        .min()
+        .unwrap_or(pattern),
+      false => pattern,
+    };
+    Self { pattern }
+  }
+
+  /// 64 bit FNV-1a hash of the canonical form, stable across runs and platforms
+  pub fn hash(&self) -> u64 {
+    self
+      .pattern
+      .iter()
+      .flatten()
+      .fold(0xcbf29ce484222325, |h, v| {
+        (h ^ *v as u64).wrapping_mul(0x100000001b3)
+      })
+  }
+}
+
+impl std::fmt::Display for TileFingerprint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:016x}", self.hash())
+  }
+}
+
+impl From<TileFingerprint> for String {
+  fn from(f: TileFingerprint) -> Self {
+    f.to_string()
+  }
+}
+
+/// normalized forms of a tile under every transform, or only itself
+fn variants(t: &TilePattern, transforms: bool) -> Vec<TilePattern> {
+  match transforms {
+    true => TileTransform::ALL
+      .iter()
+      .map(|transform| {
+        let mut v = *t;
+        transform.apply(&mut v);
+        normalize_pattern(&v)
+      })
+      .collect(),
+    false => vec![*t],
+  }
+}
+
+/// renumbers values by order of first appearance
+fn normalize_values(values: impl Iterator<Item = u8>) -> Vec<u8> {
+  let mut seen = Vec::new();
+  values
+    .map(|v| match seen.iter().position(|s| *s == v) {
+      Some(i) => i as u8,
+      None => {
+        seen.push(v);
+        seen.len() as u8 - 1
+      }
+    })
+    .collect()
+}
+
+/// lowest total cost of a square cost matrix, every row being assigned a different column
+///
+/// hungarian algorithm
+fn min_assignment(cost: &[Vec<usize>]) -> usize {
+  let n = cost.len();
+  let (mut u, mut v) = (vec![0i64; n + 1], vec![0i64; n + 1]);
+  // row assigned to every column, 1-based, 0 being none
+  let mut p = vec![0usize; n + 1];
+  let mut way = vec![0usize; n + 1];
+
+  for i in 1..=n {
+    p[0] = i;
+    let mut j0 = 0;
+    let mut minv = vec![i64::MAX; n + 1];
+    let mut used = vec![false; n + 1];
+    loop {
+      used[j0] = true;
+      let i0 = p[j0];
+      let (mut delta, mut j1) = (i64::MAX, 0);
+      for j in 1..=n {
+        if !used[j] {
+          let cur = cost[i0 - 1][j - 1] as i64 - u[i0] - v[j];
+          if cur < minv[j] {
+            minv[j] = cur;
+            way[j] = j0;
+          }
+          if minv[j] < delta {
+            delta = minv[j];
+            j1 = j;
+          }
+        }
+      }
+      for j in 0..=n {
+        if used[j] {
+          u[p[j]] += delta;
+          v[j] -= delta;
+        } else {
+          minv[j] -= delta;
+        }
+      }
+      j0 = j1;
+      if p[j0] == 0 {
+        break;
+      }
+    }
+    while j0 != 0 {
+      let j1 = way[j0];
+      p[j0] = p[j1];
+      j0 = j1;
+    }
+  }
+
+  (1..=n).map(|j| cost[p[j] - 1][j - 1]).sum()
+}
+
+/// number of differing pixels between two tiles, with the colours of `b` renumbered to match `a`
+/// as well as possible
+fn hamming(a: &TilePattern, b: &TilePattern) -> usize {
+  let a = normalize_values(a.iter().flatten().copied());
+  let b = normalize_values(b.iter().flatten().copied());
+  let n = a.iter().chain(&b).max().map_or(0, |m| *m as usize + 1);
+
+  // matching[i][j]: pixels of value i in a and j in b
+  let mut matching = vec![vec![0; n]; n];
+  for (a, b) in a.iter().zip(&b) {
+    matching[*a as usize][*b as usize] += 1;
+  }
+  // cost of giving value i of a the colour of value j of b: pixels of value i left unmatched
+  let cost: Vec<Vec<usize>> = matching
+    .iter()
+    .map(|row| {
+      let total: usize = row.iter().sum();
+      row.iter().map(|m| total - m).collect()
+    })
+    .collect();
+
+  min_assignment(&cost)
+}
+
+/// options of a fingerprint index
+#[derive(Clone, Copy, Debug)]
+pub struct FingerprintOptions {
+  /// format of the tiles of the binaries
+  pub format: TileFormat,
+  /// tiles that are flips or transpositions of each other are the same
+  pub transforms: bool,
+  /// leave out tiles of a single colour
+  pub skip_blank: bool,
+}
+
+impl Default for FingerprintOptions {
+  fn default() -> Self {
+    Self {
+      format: TileFormat::Nes,
+      transforms: true,
+      skip_blank: true,
+    }
+  }
+}
+
+/// a tile of an indexed binary
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TileLocation {
+  /// index of the binary in [`FingerprintIndex::binaries`]
+  pub binary: usize,
+  /// address of the tile in the binary
+  pub offset: usize,
+}
+
+/// tiles sharing a fingerprint
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TileGroup {
+  /// fingerprint of the tiles
+  pub fingerprint: TileFingerprint,
+  /// tiles with the fingerprint, in indexing order
+  pub locations: Vec<TileLocation>,
+}
+
+/// two groups of tiles that differ by a few pixels
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NearDuplicate {
+  /// first group
+  pub a: TileGroup,
+  /// second group
+  pub b: TileGroup,
+  /// number of differing pixels
+  pub distance: usize,
+}
+
+/// tiles of one or many binaries, indexed by fingerprint
+#[derive(Clone, Debug)]
+pub struct FingerprintIndex {
+  /// names of the indexed binaries
+  pub binaries: Vec<String>,
+  options: FingerprintOptions,
+  groups: Vec<TileGroup>,
+  lookup: HashMap<TileFingerprint, usize>,
+}
+
+impl FingerprintIndex {
+  /// creates an empty index
+  pub fn new(options: FingerprintOptions) -> Self {
+    Self {
+      binaries: Vec::new(),
+      options,
+      groups: Vec::new(),
+      lookup: HashMap::new(),
+    }
+  }
+
+  /// indexes every tile of a binary, returns the index of the binary
+  ///
+  /// tiles are read at every multiple of the tile size, trailing bytes are ignored
+  pub fn add(&mut self, name: String, b: &[u8]) -> usize {
+    let codec = self.options.format.codec();
+    let size = codec.bytes_per_tile();
+    let binary = self.binaries.len();
+    self.binaries.push(name);
+
+    for (i, chunk) in b.chunks_exact(size).enumerate() {
+      let tile = codec.decode(chunk);
+      if self.options.skip_blank && tile.iter().flatten().all(|v| *v == tile[0][0]) {
+        continue;
+      }
+
+      let location = TileLocation {
+        binary,
+        offset: i * size,
+      };
+      let fingerprint = TileFingerprint::new(&tile, self.options.transforms);
+      match self.lookup.get(&fingerprint) {
+        Some(g) => self.groups[*g].locations.push(location),
+        None => {
+          self.lookup.insert(fingerprint, self.groups.len());
+          self.groups.push(TileGroup {
+            fingerprint,
+            locations: vec![location],
+          });
+        }
+      }
+    }
+
+    binary
+  }
+
+  /// every group of tiles, in order of first appearance
+  pub fn groups(&self) -> &[TileGroup] {
+    &self.groups
+  }
+
+  /// returns the tiles sharing the fingerprint of a tile
+  pub fn get(&self, t: &TilePattern) -> Option<&TileGroup> {
+    let fingerprint = TileFingerprint::new(t, self.options.transforms);
+    self.lookup.get(&fingerprint).map(|g| &self.groups[*g])
+  }
+
+  /// groups of more than one tile
+  pub fn duplicates(&self) -> Vec<&TileGroup> {
+    self
+      .groups
+      .iter()
+      .filter(|g| g.locations.len() > 1)
+      .collect()
+  }
+
+  /// groups with tiles of more than one binary
+  pub fn shared(&self) -> Vec<&TileGroup> {
+    self
+      .groups
+      .iter()
+      .filter(|g| {
+        g.locations
+          .iter()
+          .any(|l| l.binary != g.locations[0].binary)
+      })
+      .collect()
+  }
+
+  /// pairs of groups that differ by at most `distance` pixels
+  ///
+  /// the distance is the lowest number of differing pixels under any renaming of the colours, and
+  /// under any transform with [`FingerprintOptions::transforms`] \
+  /// two tiles within `distance` pixels have at least one of `distance + 1` slices of pixels
+  /// equal up to their colours, only groups with such a slice in common are compared
+  pub fn near_duplicates(&self, distance: usize) -> Vec<NearDuplicate> {
+    let slices = (distance + 1).min(64);
+    let bounds = |s: usize| (s * 64 / slices, (s + 1) * 64 / slices);
+    let slice = |t: &TilePattern, s: usize| {
+      let (start, end) = bounds(s);
+      normalize_values(t.iter().flatten().copied().skip(start).take(end - start))
+    };
+
+    let variants: Vec<Vec<TilePattern>> = self
+      .groups
+      .iter()
+      .map(|g| variants(&g.fingerprint.pattern, self.options.transforms))
+      .collect();
+    let mut buckets: HashMap<(usize, Vec<u8>), BTreeSet<usize>> = HashMap::new();
+    for (g, vs) in variants.iter().enumerate() {
+      for v in vs {
+        for s in 0..slices {
+          buckets.entry((s, slice(v, s))).or_default().insert(g);
+        }
+      }
+    }
+
+    let mut near = Vec::new();
+    for (a, group) in self.groups.iter().enumerate() {
+      let pattern = &group.fingerprint.pattern;
+      let candidates: BTreeSet<usize> = (0..slices)
+        .filter_map(|s| buckets.get(&(s, slice(pattern, s))))
+        .flatten()
+        .copied()
+        .filter(|b| *b > a)
+        .collect();
+
+      for b in candidates {
+        let d = variants[b]
+          .iter()
+          .map(|v| hamming(pattern, v))
+          .min()
+          .unwrap_or(64);
+        if d <= distance {
+          near.push(NearDuplicate {
+            a: group.clone(),
+            b: self.groups[b].clone(),
+            distance: d,
+          });
+        }
+      }
+    }
+
+    near
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// a 2bpp tile with every colour, and the same tile with its first pixel changed
+  fn one_pixel_edit() -> (TilePattern, TilePattern) {
+    let mut a = [[0; 8]; 8];
+    for (y, row) in a.iter_mut().enumerate() {
+      for (x, v) in row.iter_mut().enumerate() {
+        *v = ((x + y * 3) % 4) as u8;
+      }
+    }
+    let mut b = a;
+    b[0][0] = 2;
+    (a, b)
+  }
+
+  #[test]
+  fn hamming_ignores_renumbering() {
+    let (a, b) = one_pixel_edit();
+    assert_eq!(hamming(&a, &b), 1);
+    assert_eq!(hamming(&a, &a.map(|row| row.map(|v| 3 - v))), 0);
+  }
+
+  #[test]
+  fn near_duplicates_first_pixel() {
+    let (a, b) = one_pixel_edit();
+    let codec = TileFormat::Nes.codec();
+
+    for transforms in [false, true] {
+      let mut index = FingerprintIndex::new(FingerprintOptions {
+        transforms,
+        ..Default::default()
+      });
+      index.add("a".to_string(), &codec.encode(&a));
+      index.add("b".to_string(), &codec.encode(&b));
+
+      let near = index.near_duplicates(4);
+      assert_eq!(near.len(), 1);
+      assert_eq!(near[0].distance, 1);
+    }
+  }
+}
@@ -0,0 +1,230 @@
+use std::fmt::Write;
+
+use crate::atlas::{Atlas, AtlasAnimation};
+
+/// a rectangle of pixels, x, y, width and height
+pub type Rect = (u32, u32, u32, u32);
+
+/// a frame of an animation, as a region of the rendered atlas
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct GodotFrame {
+  /// name of the group of the frame
+  pub group: String,
+  /// region of the group in the rendered atlas
+  pub region: Rect,
+  /// space around the region, so that the anchors of every frame of the animation line up
+  ///
+  /// x and y are added on the left and top, width and height are added to the size of the region,
+  /// like the `margin` of an `AtlasTexture`
+  pub margin: Rect,
+}
+
+/// an animation of a `SpriteFrames` resource
+#[derive(Clone, PartialEq, Debug)]
+pub struct GodotAnimation {
+  /// name of the animation
+  pub name: String,
+  /// frames, in order
+  pub frames: Vec<GodotFrame>,
+  /// frames per second
+  pub speed: f64,
+  /// whether the animation starts over after its last frame
+  pub looping: bool,
+}
+
+impl Atlas {
+  /// returns the animations of the atlas as regions of the rendered atlas
+  ///
+  /// without [`Atlas::animations`], every group becomes an animation of one frame \
+  /// every frame of an animation has the same size, its group anchor being at the same position
+  pub fn get_godot_animations(&self) -> Result<Vec<GodotAnimation>, crate::Error> {
+    let animations: Vec<(String, AtlasAnimation)> = match self.animations.is_empty() {
+      false => self
+        .animations
+        .iter()
+        .map(|(name, a)| (name.clone(), a.clone()))
+        .collect(),
+      true => {
+        let mut names: Vec<&str> = Vec::new();
+        for name in self.data.iter().filter_map(|d| d.group.as_deref()) {
+          if !names.contains(&name) {
+            names.push(name);
+          }
+        }
+        names
+          .into_iter()
+          .map(|name| {
+            let animation = AtlasAnimation {
+              frames: vec![name.to_string()],
+              ..Default::default()
+            };
+            (name.to_string(), animation)
+          })
+          .collect()
+      }
+    };
+
+    let mut out = Vec::new();
+    for (name, animation) in animations {
+      // region and anchor of every frame
+      let mut frames = Vec::new();
+      for group in &animation.frames {
+        let region = self
+          .data
+          .iter()
+          .filter(|d| d.group.as_deref() == Some(group.as_str()))
+          .fold(None, |acc: Option<(u32, u32, u32, u32)>, d| match acc {
+            Some((x0, y0, x1, y1)) => {
+              Some((x0.min(d.x), y0.min(d.y), x1.max(d.x + 8), y1.max(d.y + 8)))
+            }
+            None => Some((d.x, d.y, d.x + 8, d.y + 8)),
+          })
+          .ok_or(crate::Error::AnimationGroupError(
+            name.clone(),
+            group.clone(),
+          ))?;
+        let anchor = self.get_group_anchor(group).unwrap_or((region.0, region.1));
+        frames.push((group, region, anchor));
+      }
+
+      // space needed around the anchor by the largest frame
+      let left = frames
+        .iter()
+        .map(|(_, r, a)| a.0 as i64 - r.0 as i64)
+        .max()
+        .unwrap_or(0);
+      let top = frames
+        .iter()
+        .map(|(_, r, a)| a.1 as i64 - r.1 as i64)
+        .max()
+        .unwrap_or(0);
+      let right = frames
+        .iter()
+        .map(|(_, r, a)| r.2 as i64 - a.0 as i64)
+        .max()
+        .unwrap_or(0);
+      let bottom = frames
+        .iter()
+        .map(|(_, r, a)| r.3 as i64 - a.1 as i64)
+        .max()
+        .unwrap_or(0);
+
+      let frames = frames
+        .into_iter()
+        .map(|(group, (x0, y0, x1, y1), (ax, ay))| {
+          let (width, height) = (x1 - x0, y1 - y0);
+          let (mx, my) = (
+            left - (ax as i64 - x0 as i64),
+            top - (ay as i64 - y0 as i64),
+          );
+          GodotFrame {
+            group: group.clone(),
+            region: (x0, y0, width, height),
+            margin: (
+              mx as u32,
+              my as u32,
+              (left + right) as u32 - width,
+              (top + bottom) as u32 - height,
+            ),
+          }
+        })
+        .collect();
+
+      out.push(GodotAnimation {
+        name,
+        frames,
+        speed: animation.speed,
+        looping: animation.looping,
+      });
+    }
+
+    Ok(out)
+  }
+
+  /// exports the animations of the atlas as the text of a Godot 4 `SpriteFrames` resource (`.tres`)
+  ///
+  /// every frame is an `AtlasTexture` region of the texture at `texture_path`, like
+  /// `res://sprites.png`, which is the atlas rendered without its background, see
+  /// [`Atlas::get_godot_animations`]
+  pub fn export_godot_sprite_frames(&self, texture_path: &str) -> Result<String, crate::Error> {
+    let animations = self.get_godot_animations()?;
+    let frames: usize = animations.iter().map(|a| a.frames.len()).sum();
+    let rect = |r: &Rect| format!("Rect2({}, {}, {}, {})", r.0, r.1, r.2, r.3);
+    let string = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut out = String::new();
+    writeln!(
+      out,
+      "[gd_resource type=\"SpriteFrames\" load_steps={} format=3]\n",
+      frames + 2
+    )
+    .unwrap();
+    writeln!(
+      out,
+      "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"1_atlas\"]\n",
+      string(texture_path)
+    )
+    .unwrap();
+
+    let id = |a: usize, f: usize| format!("AtlasTexture_{}_{}", a, f);
+
+    for (a, animation) in animations.iter().enumerate() {
+      for (f, frame) in animation.frames.iter().enumerate() {
+        writeln!(
+          out,
+          "[sub_resource type=\"AtlasTexture\" id=\"{}\"]",
+          id(a, f)
+        )
+        .unwrap();
+        writeln!(out, "atlas = ExtResource(\"1_atlas\")").unwrap();
+        writeln!(out, "region = {}", rect(&frame.region)).unwrap();
+        if frame.margin != (0, 0, 0, 0) {
+          writeln!(out, "margin = {}", rect(&frame.margin)).unwrap();
+        }
+        writeln!(out).unwrap();
+      }
+    }
+
+    let animations: Vec<String> = animations
+      .iter()
+      .enumerate()
+      .map(|(a, animation)| {
+        let frames: Vec<String> = (0..animation.frames.len())
+          .map(|f| {
+            format!(
+              "{{\n\"duration\": 1.0,\n\"texture\": SubResource(\"{}\")\n}}",
+              id(a, f)
+            )
+          })
+          .collect();
+        format!(
+          "{{\n\"frames\": [{}],\n\"loop\": {},\n\"name\": &\"{}\",\n\"speed\": {:?}\n}}",
+          frames.join(", "),
+          animation.looping,
+          string(&animation.name),
+          animation.speed
+        )
+      })
+      .collect();
+    writeln!(out, "[resource]").unwrap();
+    writeln!(out, "animations = [{}]", animations.join(", ")).unwrap();
+
+    Ok(out)
+  }
+
+  /// returns raw bytes of the atlas rendered without its background, the texture of
+  /// [`Atlas::export_godot_sprite_frames`]
+  ///
+  /// when the atlas has a background, its data keep being drawn like sprites, earlier data in front
+  /// and color 0 transparent
+  pub fn get_godot_texture(&self) -> Result<Vec<u8>, crate::Error> {
+    let mut atlas = self.clone();
+    if atlas.background.take().is_some() {
+      atlas.data.reverse();
+      for d in &mut atlas.data {
+        d.cbg = None;
+      }
+    }
+    atlas.get_png_bytes()
+  }
+}
@@ -0,0 +1,334 @@
+use image::RgbaImage;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{
+  atlas::{Atlas, AtlasData, AtlasSource},
+  codec::{TileFormat, TilePattern, write_tiles},
+  find::TileTransform,
+  pal::{PaletteFormat, nearest_color},
+  ppu::{PpuMask, PpuType},
+};
+
+/// palette indices of the pixels of an 8x8 cell, `None` if transparent
+type Cell = [[Option<usize>; 8]; 8];
+
+/// options of the image importer
+#[derive(Clone, Copy, Debug)]
+pub struct ImportOptions {
+  /// format of the tiles to create
+  pub format: TileFormat,
+  /// maximum number of sub-palettes
+  pub sub_palettes: usize,
+  /// palette index of colour 0, shared by every sub-palette
+  ///
+  /// if `None`, colour 0 is transparent when the image has transparent pixels, and the most used
+  /// colour otherwise
+  pub backdrop: Option<usize>,
+  /// reuse tiles that are flips of other tiles
+  pub flips: bool,
+}
+
+impl Default for ImportOptions {
+  fn default() -> Self {
+    Self {
+      format: TileFormat::Nes,
+      sub_palettes: 4,
+      backdrop: None,
+      flips: true,
+    }
+  }
+}
+
+/// a tile of the image that could not be imported exactly
+///
+/// the tile is still imported, its missing colours being replaced by the closest colours of the
+/// sub-palette that fits it best
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ImportIssue {
+  /// the tile has more colours than a sub-palette can hold
+  TooManyColors { x: u32, y: u32, colors: Vec<usize> },
+  /// the colours of the tile do not fit any sub-palette
+  NoSubPalette { x: u32, y: u32, colors: Vec<usize> },
+}
+
+/// an image converted to tiles
+#[derive(Clone, Debug)]
+pub struct ImportedImage {
+  /// unique tiles
+  pub tiles: Vec<TilePattern>,
+  /// encoded tiles
+  pub chr: Vec<u8>,
+  /// format of the tiles
+  pub format: TileFormat,
+  /// colour 0, `None` if transparent
+  pub backdrop: Option<usize>,
+  /// sub-palettes, colours 1 and up
+  pub sub_palettes: Vec<Vec<usize>>,
+  /// one data per 8x8 cell of the image, fully transparent cells are left out
+  pub data: Vec<AtlasData>,
+  /// tiles that could not be imported exactly
+  pub issues: Vec<ImportIssue>,
+}
+
+impl std::fmt::Display for ImportIssue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ImportIssue::TooManyColors { x, y, colors } => write!(
+        f,
+        "tile ({}, {}): {} colors {:X?}, too many for a sub-palette",
+        x,
+        y,
+        colors.len(),
+        colors
+      ),
+      ImportIssue::NoSubPalette { x, y, colors } => write!(
+        f,
+        "tile ({}, {}): colors {:X?} do not fit any sub-palette",
+        x, y, colors
+      ),
+    }
+  }
+}
+
+/// converts an image to tiles
+///
+/// the image is quantized to the closest colours of the palette and cut into 8x8 tiles, every tile
+/// is given one of the sub-palettes, and identical tiles are only kept once \
+/// partial tiles on the right and bottom edges are padded with colour 0
+pub fn import_image(image: &RgbaImage, pal: &[[u8; 3]], options: &ImportOptions) -> ImportedImage {
+  let codec = options.format.codec();
+  let per_sub_palette = (1 << codec.bits_per_pixel()) - 1;
+  let columns = image.width().div_ceil(8);
+  let rows = image.height().div_ceil(8);
+
+  // --- QUANTIZATION ---
+  // palette index of every pixel, `None` if transparent
+  let mut cache: HashMap<[u8; 3], usize> = HashMap::new();
+  let pixel = |x: u32, y: u32, cache: &mut HashMap<[u8; 3], usize>| {
+    if x >= image.width() || y >= image.height() {
+      return None;
+    }
+    let p = image.get_pixel(x, y).0;
+    if p[3] < 128 {
+      return None;
+    }
+    let c = [p[0], p[1], p[2]];
+    Some(*cache.entry(c).or_insert_with(|| nearest_color(pal, c)))
+  };
+  let cells: Vec<(u32, u32, Cell)> = (0..rows)
+    .flat_map(|ty| (0..columns).map(move |tx| (tx * 8, ty * 8)))
+    .map(|(x, y)| {
+      let mut cell = [[None; 8]; 8];
+      for (cy, row) in cell.iter_mut().enumerate() {
+        for (cx, v) in row.iter_mut().enumerate() {
+          *v = pixel(x + cx as u32, y + cy as u32, &mut cache);
+        }
+      }
+      (x, y, cell)
+    })
+    .collect();
+
+  let backdrop = options.backdrop.or_else(|| {
+    let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut transparent = false;
+    for v in cells.iter().flat_map(|(_, _, c)| c.iter().flatten()) {
+      match v {
+        Some(v) => *counts.entry(*v).or_default() += 1,
+        None => transparent = true,
+      }
+    }
+    match transparent {
+      true => None,
+      false => counts.iter().max_by_key(|(_, n)| **n).map(|(v, _)| *v),
+    }
+  });
+
+  // colours of every cell, colour 0 excluded
+  let colors: Vec<BTreeSet<usize>> = cells
+    .iter()
+    .map(|(_, _, c)| {
+      c.iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .filter(|v| Some(*v) != backdrop)
+        .collect()
+    })
+    .collect();
+
+  // --- SUB-PALETTES ---
+  let mut order: Vec<usize> = (0..cells.len()).collect();
+  order.sort_by_key(|i| std::cmp::Reverse(colors[*i].len()));
+
+  let mut sub_palettes: Vec<BTreeSet<usize>> = Vec::new();
+  let mut assigned: Vec<Option<usize>> = vec![None; cells.len()];
+  for i in order.iter().copied() {
+    if colors[i].len() > per_sub_palette {
+      continue;
+    }
+
+    // the sub-palette that grows the least
+    let best = sub_palettes
+      .iter()
+      .enumerate()
+      .map(|(s, sp)| (s, sp.union(&colors[i]).count()))
+      .filter(|(_, size)| *size <= per_sub_palette)
+      .min_by_key(|(s, size)| (*size - sub_palettes[*s].len(), *s));
+
+    match best {
+      Some((s, _)) => {
+        sub_palettes[s].extend(colors[i].iter().copied());
+        assigned[i] = Some(s);
+      }
+      None if sub_palettes.len() < options.sub_palettes => {
+        sub_palettes.push(colors[i].clone());
+        assigned[i] = Some(sub_palettes.len() - 1);
+      }
+      None => {}
+    }
+  }
+  if sub_palettes.is_empty() {
+    sub_palettes.push(BTreeSet::new());
+  }
+  let sub_palettes: Vec<Vec<usize>> = sub_palettes
+    .into_iter()
+    .map(|sp| sp.into_iter().collect())
+    .collect();
+
+  // --- TILES ---
+  let mut issues = Vec::new();
+  let mut tiles: Vec<TilePattern> = Vec::new();
+  let mut known: HashMap<TilePattern, (usize, TileTransform)> = HashMap::new();
+  let mut data = Vec::new();
+  let transforms: Vec<TileTransform> = TileTransform::ALL
+    .into_iter()
+    .filter(|t| !t.transpose && (options.flips || *t == TileTransform::default()))
+    .collect();
+
+  for (i, (x, y, cell)) in cells.iter().enumerate() {
+    let (x, y) = (*x, *y);
+    if cell.iter().flatten().all(|v| v.is_none()) {
+      continue;
+    }
+
+    let s = match assigned[i] {
+      Some(s) => s,
+      None => {
+        let cell_colors: Vec<usize> = colors[i].iter().copied().collect();
+        issues.push(match cell_colors.len() > per_sub_palette {
+          true => ImportIssue::TooManyColors {
+            x,
+            y,
+            colors: cell_colors,
+          },
+          false => ImportIssue::NoSubPalette {
+            x,
+            y,
+            colors: cell_colors,
+          },
+        });
+        (0..sub_palettes.len())
+          .max_by_key(|s| {
+            let overlap = sub_palettes[*s]
+              .iter()
+              .filter(|c| colors[i].contains(c))
+              .count();
+            (overlap, std::cmp::Reverse(*s))
+          })
+          .unwrap_or(0)
+      }
+    };
+    let sub_palette = &sub_palettes[s];
+
+    // pixel values, colours missing from the sub-palette become the closest one
+    let tile: TilePattern = cell.map(|row| {
+      row.map(|v| match v {
+        None => 0,
+        Some(v) if Some(v) == backdrop => 0,
+        Some(v) => match sub_palette.iter().position(|c| *c == v) {
+          Some(p) => p as u8 + 1,
+          None => {
+            let colors: Vec<[u8; 3]> = sub_palette.iter().map(|c| pal[*c]).collect();
+            nearest_color(&colors, pal[v]) as u8 + 1
+          }
+        },
+      })
+    });
+
+    let (chr_index, transform) = match known.get(&tile) {
+      Some(k) => *k,
+      None => {
+        tiles.push(tile);
+        let index = tiles.len() - 1;
+        for t in &transforms {
+          let mut variant = tile;
+          t.apply(&mut variant);
+          known.entry(variant).or_insert((index, *t));
+        }
+        (index, TileTransform::default())
+      }
+    };
+
+    let mut values: Vec<usize> = sub_palette.clone();
+    values.resize(per_sub_palette, backdrop.unwrap_or(0));
+    let opaque_backdrop = cell.iter().flatten().any(|v| v.is_some() && *v == backdrop);
+
+    data.push(AtlasData {
+      chr_index,
+      c0: values.first().copied().unwrap_or(0),
+      c1: values.get(1).copied().unwrap_or(0),
+      c2: values.get(2).copied().unwrap_or(0),
+      colors: match values.len() > 3 {
+        true => values,
+        false => Vec::new(),
+      },
+      x,
+      y,
+      flip_x: transform.flip_x,
+      flip_y: transform.flip_y,
+      cbg: backdrop.filter(|_| opaque_backdrop),
+      ..Default::default()
+    });
+  }
+
+  ImportedImage {
+    chr: write_tiles(&tiles, &*codec),
+    tiles,
+    format: options.format,
+    backdrop,
+    sub_palettes,
+    data,
+    issues,
+  }
+}
+
+impl ImportedImage {
+  /// returns an atlas reproducing the image, reading the chr from `binary` and the colours from
+  /// `palette`
+  pub fn to_atlas(&self, binary: String, palette: String) -> Atlas {
+    Atlas {
+      source: AtlasSource {
+        binary,
+        start: 0,
+        length: self.chr.len(),
+        banks: Vec::new(),
+        format: self.format,
+        fds: None,
+        compression: None,
+      },
+      sources: BTreeMap::new(),
+      palette,
+      palette_format: PaletteFormat::default(),
+      ppu: PpuType::default(),
+      mask: PpuMask::default(),
+      data: self.data.clone(),
+      background: None,
+      groups: BTreeMap::new(),
+      animations: BTreeMap::new(),
+    }
+  }
+}
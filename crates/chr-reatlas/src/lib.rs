@@ -12,9 +12,28 @@ use image::ImageError;
 use thiserror::Error;
 
 pub mod atlas;
+pub mod binary;
 pub mod chr;
+pub mod codec;
+pub mod compression;
+pub mod diff;
+pub mod fds;
+pub mod find;
+pub mod fingerprint;
+pub mod godot;
+pub mod import;
+pub mod lint;
+pub mod metasprite;
+pub mod nametable;
+pub mod nesst;
 pub mod pal;
+pub mod ppu;
 pub mod render;
+pub mod repack;
+pub mod scan;
+pub mod screenshot;
+pub mod text;
+pub mod tiled;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -24,6 +43,9 @@ pub enum Error {
   #[error("io error: {0}")]
   IOError(io::Error),
 
+  #[error("zip error: {0}")]
+  ZipError(zip::result::ZipError),
+
   #[error("bytes error: {0}")]
   BytesError(TryFromSliceError),
 
@@ -33,6 +55,114 @@ pub enum Error {
   #[error("atlas chr index error: {0}")]
   AtlasWrongIndexError(usize),
 
+  #[error("atlas unknown source error: {0}")]
+  AtlasUnknownSourceError(String),
+
   #[error("atlas parse error: {0} - {1}")]
   AtlasParseError(String, ParseIntError),
+
+  #[error("tile size error: {0} bytes is not a multiple of {1} bytes")]
+  TileSizeError(usize, usize),
+
+  #[error("tile format parse error: {0}")]
+  TileFormatParseError(String),
+
+  #[error("atlas colors error: chr {0} needs {1} colors")]
+  AtlasColorsError(usize, usize),
+
+  #[error("atlas palette index error: color {0} is out of a palette of {1} colors")]
+  AtlasPaletteIndexError(usize, usize),
+
+  #[error("nametable size error: expected 1024 bytes, got {0}")]
+  NametableSizeError(usize),
+
+  #[error("compression parse error: {0}")]
+  CompressionParseError(String),
+
+  #[error("decompression error: invalid or truncated stream at byte {0}")]
+  DecompressionError(usize),
+
+  #[error("fds block error: expected block {1} at offset {0:#X}")]
+  FdsBlockError(usize, u8),
+
+  #[error("fds file error: no file {1} on side {0}")]
+  FdsFileError(usize, usize),
+
+  #[error(
+    "repack format error: data {0} reads source {1}, whose tile format differs from the main source"
+  )]
+  RepackFormatError(usize, String),
+
+  #[error("metasprite format parse error: {0}")]
+  MetaspriteFormatParseError(String),
+
+  #[error("metasprite transpose error: data {0} is transposed, sprites can only be flipped")]
+  MetaspriteTransposeError(usize),
+
+  #[error("metasprite tile error: data {0} uses tile {1:#X}, sprites address 256 tiles")]
+  MetaspriteTileError(usize, usize),
+
+  #[error("metasprite offset error: data {0} is at ({1}, {2}) from its anchor, out of -128 to 127")]
+  MetaspriteOffsetError(usize, i64, i64),
+
+  #[error("metasprite sub-palette error: data {0} does not fit the 4 sprite sub-palettes")]
+  MetaspriteSubPaletteError(usize),
+
+  #[error("metasprite attribute bit error: bit {0} is out of the attribute byte")]
+  MetaspriteAttributeBitError(u8),
+
+  #[error("metasprite fields error: a metasprite record needs at least one field")]
+  MetaspriteFieldsError,
+
+  #[error("metasprite count error: {0} sprites is more than the 255 a metasprite table can have")]
+  MetaspriteCountError(usize),
+
+  #[error("metasprite identifier error: groups {0} and {1} are both written {2}")]
+  MetaspriteIdentifierError(String, String, String),
+
+  #[error("metasprite table error: table at {0:#X} runs past the end of the binary")]
+  MetaspriteTableError(usize),
+
+  #[error("metasprite group error: the atlas already has a group named {0}")]
+  MetaspriteGroupError(String),
+
+  #[error(
+    "metasprite position error: sprite {0} of the table lands at ({1}, {2}), out of the atlas"
+  )]
+  MetaspritePositionError(usize, i64, i64),
+
+  #[error("nesst palette size error: expected 16 or 32 bytes, got {0}")]
+  NesstPaletteSizeError(usize),
+
+  #[error("nesst bank size error: {0} bytes is too short for a metasprite bank")]
+  NesstBankSizeError(usize),
+
+  #[error("nesst bank full error: {0} groups do not fit the 256 metasprites of a bank")]
+  NesstBankFullError(usize),
+
+  #[error("nesst sprite count error: group {0} has {1} sprites, a bank metasprite holds 64")]
+  NesstSpriteCountError(String, usize),
+
+  #[error(
+    "nesst sprite position error: a sprite of group {0} lands at ({1}, {2}), out of the bank"
+  )]
+  NesstSpritePositionError(String, i64, i64),
+
+  #[error("nesst session error: invalid value for {0}")]
+  NesstSessionError(String),
+
+  #[error("tiled parse error: {0}")]
+  TiledParseError(String),
+
+  #[error("tiled gid error: tile {0:#X} is in no tileset")]
+  TiledGidError(u32),
+
+  #[error("animation group error: frame {1} of animation {0} has no data")]
+  AnimationGroupError(String, String),
+
+  #[error("text table parse error: line {0}: {1}")]
+  TextTableParseError(usize, String),
+
+  #[error("text encode error: no code for {0:?} in the table")]
+  TextEncodeError(char),
 }
@@ -0,0 +1,215 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::atlas::Atlas;
+
+/// maximum number of sprites the ppu can display on a scanline
+pub const SPRITES_PER_SCANLINE: usize = 8;
+/// number of sprite sub-palettes
+pub const SPRITE_SUB_PALETTES: usize = 4;
+/// width of the screen, in pixels
+pub const SCREEN_WIDTH: u32 = 256;
+/// height of the screen, in pixels
+pub const SCREEN_HEIGHT: u32 = 240;
+
+/// a hardware constraint broken by an atlas
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum LintIssue {
+  /// more than 8 sprites share the scanlines from `first` to `last`
+  SpritesPerScanline {
+    first: u32,
+    last: u32,
+    /// indices of the data on those scanlines
+    data: Vec<usize>,
+  },
+  /// more than 4 sprite sub-palettes are needed
+  TooManySubPalettes {
+    /// every needed sub-palette, `None` where the colour is never used
+    sub_palettes: Vec<[Option<usize>; 3]>,
+  },
+  /// the colours of a data do not fit any of the 4 most used sub-palettes
+  SubPaletteMismatch { data: usize, colors: Vec<usize> },
+  /// a data is not entirely on the 256x240 screen
+  OffScreen { data: usize, x: u32, y: u32 },
+}
+
+/// a sprite sub-palette, and the data using it
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SpriteSubPalette {
+  /// colors 1 to 3, `None` where the colour is never used
+  pub colors: [Option<usize>; 3],
+  /// indices of the data using the sub-palette
+  pub data: Vec<usize>,
+}
+
+/// whether colours needed by a data fit a sub-palette, `None` fitting any colour
+pub(crate) fn sub_palette_fits(sp: &[Option<usize>; 3], need: &[Option<usize>; 3]) -> bool {
+  sp.iter()
+    .zip(need.iter())
+    .all(|(s, n)| s.is_none() || n.is_none() || s == n)
+}
+
+/// adds the colours needed by a data to a sub-palette they fit
+pub(crate) fn sub_palette_merge(sp: &mut [Option<usize>; 3], need: &[Option<usize>; 3]) {
+  for (s, n) in sp.iter_mut().zip(need.iter()) {
+    if s.is_none() {
+      *s = *n;
+    }
+  }
+}
+
+impl Atlas {
+  /// returns the colours 1 to 3 actually used by the pixels of every data, `None` where unused
+  pub(crate) fn sprite_needs(&self) -> Result<Vec<[Option<usize>; 3]>, crate::Error> {
+    let chrs = self.get_all_chrs()?;
+
+    let mut needs = Vec::new();
+    for d in &self.data {
+      let chr = chrs.get(d)?;
+      let colors = self.get_colors(d)?;
+      let mut need = [None; 3];
+      for v in chr.iter().flatten().map(|v| *v as usize) {
+        if v != 0 {
+          if v > need.len() || v > colors.len() {
+            return Err(crate::Error::AtlasColorsError(d.chr_index, v));
+          }
+          need[v - 1] = Some(colors[v - 1]);
+        }
+      }
+      needs.push(need);
+    }
+
+    Ok(needs)
+  }
+
+  /// groups the data of the atlas into sprite sub-palettes
+  ///
+  /// colours that are not used by the pixels of a tile are not taken into account, data needing the
+  /// most colours are placed first, each in the first sub-palette it fits
+  pub fn sprite_sub_palettes(&self) -> Result<Vec<SpriteSubPalette>, crate::Error> {
+    let mut needs: Vec<(usize, [Option<usize>; 3])> =
+      self.sprite_needs()?.into_iter().enumerate().collect();
+    needs.sort_by_key(|(_, need)| std::cmp::Reverse(need.iter().flatten().count()));
+
+    let mut sub_palettes: Vec<SpriteSubPalette> = Vec::new();
+    for (j, need) in needs {
+      match sub_palettes
+        .iter_mut()
+        .find(|sp| sub_palette_fits(&sp.colors, &need))
+      {
+        Some(sp) => {
+          sub_palette_merge(&mut sp.colors, &need);
+          sp.data.push(j);
+        }
+        None => sub_palettes.push(SpriteSubPalette {
+          colors: need,
+          data: vec![j],
+        }),
+      }
+    }
+
+    Ok(sub_palettes)
+  }
+
+  /// checks the atlas against the sprite constraints of the ppu
+  ///
+  /// colours that are not used by the pixels of a tile are not taken into account when grouping
+  /// tiles into sub-palettes
+  pub fn lint(&self) -> Result<Vec<LintIssue>, crate::Error> {
+    let mut issues = Vec::new();
+
+    // --- SPRITES PER SCANLINE ---
+    let height = self.data.iter().fold(0, |acc, d| acc.max(d.y + 8));
+    let mut current: Option<(u32, Vec<usize>)> = None;
+    for line in 0..=height {
+      let on_line: Vec<usize> = self
+        .data
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.y <= line && line < d.y + 8)
+        .map(|(j, _)| j)
+        .collect();
+
+      let over = on_line.len() > SPRITES_PER_SCANLINE;
+      match current.take() {
+        Some((first, data)) if over && data == on_line => current = Some((first, data)),
+        Some((first, data)) => {
+          issues.push(LintIssue::SpritesPerScanline {
+            first,
+            last: line - 1,
+            data,
+          });
+          if over {
+            current = Some((line, on_line));
+          }
+        }
+        None if over => current = Some((line, on_line)),
+        None => {}
+      }
+    }
+
+    // --- SUB-PALETTES ---
+    let mut sub_palettes = self.sprite_sub_palettes()?;
+    if sub_palettes.len() > SPRITE_SUB_PALETTES {
+      sub_palettes.sort_by_key(|sp| std::cmp::Reverse(sp.data.len()));
+
+      issues.push(LintIssue::TooManySubPalettes {
+        sub_palettes: sub_palettes.iter().map(|sp| sp.colors).collect(),
+      });
+
+      let mut mismatches: Vec<usize> = sub_palettes[SPRITE_SUB_PALETTES..]
+        .iter()
+        .flat_map(|sp| sp.data.clone())
+        .collect();
+      mismatches.sort();
+      for j in mismatches {
+        let d = &self.data[j];
+        issues.push(LintIssue::SubPaletteMismatch {
+          data: j,
+          colors: self.get_colors(d)?,
+        });
+      }
+    }
+
+    // --- SCREEN BOUNDS ---
+    for (j, d) in self.data.iter().enumerate() {
+      if d.x + 8 > SCREEN_WIDTH || d.y + 8 > SCREEN_HEIGHT {
+        issues.push(LintIssue::OffScreen {
+          data: j,
+          x: d.x,
+          y: d.y,
+        });
+      }
+    }
+
+    Ok(issues)
+  }
+}
+
+impl std::fmt::Display for LintIssue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LintIssue::SpritesPerScanline { first, last, data } => write!(
+        f,
+        "scanlines {}-{}: {} sprites (data {:?})",
+        first,
+        last,
+        data.len(),
+        data
+      ),
+      LintIssue::TooManySubPalettes { sub_palettes } => {
+        write!(f, "{} sub-palettes needed", sub_palettes.len())
+      }
+      LintIssue::SubPaletteMismatch { data, colors } => write!(
+        f,
+        "data {}: colors {:X?} do not fit the sprite sub-palettes",
+        data, colors
+      ),
+      LintIssue::OffScreen { data, x, y } => {
+        write!(f, "data {}: off screen at ({}, {})", data, x, y)
+      }
+    }
+  }
+}
@@ -0,0 +1,691 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use crate::{
+  atlas::{Atlas, AtlasData, AtlasGroup},
+  lint::{SPRITE_SUB_PALETTES, sub_palette_fits, sub_palette_merge},
+};
+
+/// x offset ending a metasprite table, like neslib's `oam_meta_spr`
+pub const METASPRITE_END: u8 = 0x80;
+
+/// most records a metasprite table can have, as many as a count byte can give
+pub const METASPRITE_MAX_SPRITES: usize = 255;
+
+/// a sprite of a metasprite, as stored in a table
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct MetaspriteEntry {
+  /// x offset from the anchor
+  pub x: i8,
+  /// y offset from the anchor
+  pub y: i8,
+  /// tile number
+  pub tile: u8,
+  /// OAM attribute byte
+  pub attribute: u8,
+}
+
+/// a group of an atlas, as a list of sprites
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Metasprite {
+  /// name of the group
+  pub name: String,
+  /// sprites, in the order of the atlas data
+  pub entries: Vec<MetaspriteEntry>,
+  /// colours 1 to 3 of the sprite sub-palettes used by the sprites, `None` where unused
+  pub sub_palettes: [[Option<usize>; 3]; SPRITE_SUB_PALETTES],
+}
+
+/// source formats metasprites can be exported to
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum MetaspriteFormat {
+  /// ca65 `.byte` tables, with an `.export` for every table
+  Ca65,
+  /// asm6 `.db` tables
+  Asm6,
+  /// C arrays for neslib's `oam_meta_spr`
+  Neslib,
+}
+
+impl MetaspriteFormat {
+  /// every metasprite format, with its name
+  pub const ALL: &[(&str, MetaspriteFormat)] = &[
+    ("ca65", MetaspriteFormat::Ca65),
+    ("asm6", MetaspriteFormat::Asm6),
+    ("neslib", MetaspriteFormat::Neslib),
+  ];
+}
+
+impl std::str::FromStr for MetaspriteFormat {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    MetaspriteFormat::ALL
+      .iter()
+      .find(|(name, _)| *name == s)
+      .map(|(_, f)| *f)
+      .ok_or(crate::Error::MetaspriteFormatParseError(s.to_string()))
+  }
+}
+
+/// a field of a metasprite record
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum MetaspriteField {
+  /// x offset
+  X,
+  /// y offset
+  Y,
+  /// tile number
+  Tile,
+  /// attribute byte
+  Attribute,
+  /// ignored byte
+  Skip,
+}
+
+/// how the end of a metasprite table is found
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum MetaspriteEnd {
+  /// the table ends with a record starting with this byte
+  Terminator(u8),
+  /// the table starts with a byte giving its number of records
+  Count,
+  /// the table has this number of records
+  Fixed(usize),
+}
+
+/// meaning of the bits of a metasprite attribute byte, OAM by default
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct AttributeLayout {
+  /// position of the lowest bit of the sub-palette
+  pub palette_shift: u8,
+  /// mask of the sub-palette, after shifting
+  pub palette_mask: u8,
+  /// bit flipping the sprite horizontally
+  pub flip_x: Option<u8>,
+  /// bit flipping the sprite vertically
+  pub flip_y: Option<u8>,
+  /// bit putting the sprite behind the background
+  pub behind_background: Option<u8>,
+}
+
+impl Default for AttributeLayout {
+  fn default() -> Self {
+    Self {
+      palette_shift: 0,
+      palette_mask: 0x03,
+      flip_x: Some(6),
+      flip_y: Some(7),
+      behind_background: Some(5),
+    }
+  }
+}
+
+impl AttributeLayout {
+  /// checks that every bit of the layout is in the attribute byte
+  pub fn check(&self) -> Result<(), crate::Error> {
+    let bits = [self.flip_x, self.flip_y, self.behind_background];
+    match bits
+      .into_iter()
+      .flatten()
+      .chain([self.palette_shift])
+      .find(|bit| *bit >= 8)
+    {
+      Some(bit) => Err(crate::Error::MetaspriteAttributeBitError(bit)),
+      None => Ok(()),
+    }
+  }
+
+  /// sets the sub-palette, flips and priority of a sprite from an attribute byte
+  ///
+  /// bits out of the byte read as 0, see [`AttributeLayout::check`]
+  pub fn decode(&self, v: u8, sprite: &mut TableSprite) {
+    let shift = |bit: u8| v.checked_shr(bit as u32).unwrap_or(0);
+    let bit = |bit: Option<u8>| bit.is_some_and(|bit| shift(bit) & 1 == 1);
+    sprite.sub_palette = (shift(self.palette_shift) & self.palette_mask) as usize;
+    sprite.flip_x = bit(self.flip_x);
+    sprite.flip_y = bit(self.flip_y);
+    sprite.behind_background = bit(self.behind_background);
+  }
+}
+
+/// layout of a metasprite table in a binary, neslib's `oam_meta_spr` layout by default
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MetaspriteLayout {
+  /// fields of a record, in order, one byte each
+  pub fields: Vec<MetaspriteField>,
+  /// whether x and y offsets are signed
+  pub signed: bool,
+  /// how the end of the table is found
+  pub end: MetaspriteEnd,
+  /// meaning of the bits of the attribute byte
+  pub attribute: AttributeLayout,
+  /// added to the tile numbers, to point into a larger chr source
+  pub tile_offset: usize,
+}
+
+impl Default for MetaspriteLayout {
+  fn default() -> Self {
+    Self {
+      fields: vec![
+        MetaspriteField::X,
+        MetaspriteField::Y,
+        MetaspriteField::Tile,
+        MetaspriteField::Attribute,
+      ],
+      signed: true,
+      end: MetaspriteEnd::Terminator(METASPRITE_END),
+      attribute: AttributeLayout::default(),
+      tile_offset: 0,
+    }
+  }
+}
+
+/// a sprite read from a metasprite table
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TableSprite {
+  /// x offset from the anchor
+  pub x: i64,
+  /// y offset from the anchor
+  pub y: i64,
+  /// tile number, with the tile offset of the layout
+  pub tile: usize,
+  /// sprite sub-palette
+  pub sub_palette: usize,
+  /// flip x
+  pub flip_x: bool,
+  /// flip y
+  pub flip_y: bool,
+  /// sprite priority
+  pub behind_background: bool,
+}
+
+impl From<MetaspriteEntry> for TableSprite {
+  fn from(e: MetaspriteEntry) -> Self {
+    let mut sprite = TableSprite {
+      x: e.x as i64,
+      y: e.y as i64,
+      tile: e.tile as usize,
+      sub_palette: 0,
+      flip_x: false,
+      flip_y: false,
+      behind_background: false,
+    };
+    AttributeLayout::default().decode(e.attribute, &mut sprite);
+    sprite
+  }
+}
+
+impl MetaspriteLayout {
+  /// checks that records have fields, that fixed tables are not too long, and the attribute layout
+  pub fn check(&self) -> Result<(), crate::Error> {
+    if self.fields.is_empty() {
+      return Err(crate::Error::MetaspriteFieldsError);
+    }
+    match self.end {
+      MetaspriteEnd::Fixed(n) if n > METASPRITE_MAX_SPRITES => {
+        Err(crate::Error::MetaspriteCountError(n))
+      }
+      _ => self.attribute.check(),
+    }
+  }
+
+  /// reads the metasprite table at `offset`, returns its sprites and its size in bytes
+  pub fn read(&self, b: &[u8], offset: usize) -> Result<(Vec<TableSprite>, usize), crate::Error> {
+    self.check()?;
+    let byte = |position: usize| {
+      b.get(position)
+        .copied()
+        .ok_or(crate::Error::MetaspriteTableError(offset))
+    };
+    let offset_value = |v: u8| match self.signed {
+      true => v as i8 as i64,
+      false => v as i64,
+    };
+
+    let mut position = offset;
+    let count = match self.end {
+      MetaspriteEnd::Terminator(_) => None,
+      MetaspriteEnd::Count => {
+        position += 1;
+        Some(byte(offset)? as usize)
+      }
+      MetaspriteEnd::Fixed(n) => Some(n),
+    };
+
+    let mut sprites = Vec::new();
+    loop {
+      match (self.end, count) {
+        (MetaspriteEnd::Terminator(t), _) if byte(position)? == t => {
+          position += 1;
+          break;
+        }
+        (_, Some(count)) if sprites.len() == count => break,
+        _ => {}
+      }
+
+      let mut sprite = TableSprite {
+        x: 0,
+        y: 0,
+        tile: self.tile_offset,
+        sub_palette: 0,
+        flip_x: false,
+        flip_y: false,
+        behind_background: false,
+      };
+      for field in &self.fields {
+        let v = byte(position)?;
+        position += 1;
+        match field {
+          MetaspriteField::X => sprite.x = offset_value(v),
+          MetaspriteField::Y => sprite.y = offset_value(v),
+          MetaspriteField::Tile => sprite.tile = self.tile_offset + v as usize,
+          MetaspriteField::Attribute => self.attribute.decode(v, &mut sprite),
+          MetaspriteField::Skip => {}
+        }
+      }
+      sprites.push(sprite);
+    }
+
+    Ok((sprites, position - offset))
+  }
+}
+
+/// returns the OAM attribute byte of a data: sub-palette, priority and flips
+pub fn oam_attribute(d: &AtlasData, sub_palette: usize) -> u8 {
+  (sub_palette as u8 & 0x03)
+    | ((d.behind_background as u8) << 5)
+    | ((d.flip_x as u8) << 6)
+    | ((d.flip_y as u8) << 7)
+}
+
+/// turns a group name into an identifier
+fn identifier(name: &str) -> String {
+  let mut id: String = name
+    .chars()
+    .map(|c| match c.is_ascii_alphanumeric() {
+      true => c,
+      false => '_',
+    })
+    .collect();
+  if !id.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+    id.insert(0, '_');
+  }
+  id
+}
+
+/// turns group names into identifiers, failing if two names give the same identifier
+fn identifiers(names: &[&str]) -> Result<Vec<String>, crate::Error> {
+  let ids: Vec<String> = names.iter().map(|name| identifier(name)).collect();
+  for (i, id) in ids.iter().enumerate() {
+    if let Some(k) = ids[..i].iter().position(|other| other == id) {
+      return Err(crate::Error::MetaspriteIdentifierError(
+        names[k].to_string(),
+        names[i].to_string(),
+        id.clone(),
+      ));
+    }
+  }
+  Ok(ids)
+}
+
+impl Atlas {
+  /// returns the anchor of a group, or the top left corner of its data
+  ///
+  /// returns `None` for a group without an anchor or data
+  pub fn get_group_anchor(&self, name: &str) -> Option<(u32, u32)> {
+    match self.groups.get(name) {
+      Some(g) => Some((g.x, g.y)),
+      None => self
+        .data
+        .iter()
+        .filter(|d| d.group.as_deref() == Some(name))
+        .fold(None, |acc, d| match acc {
+          Some((x, y)) => Some((d.x.min(x), d.y.min(y))),
+          None => Some((d.x, d.y)),
+        }),
+    }
+  }
+
+  /// places data in the sprite sub-palettes, returns the sub-palette of each data of `data`
+  ///
+  /// data keep their [`AtlasData::sub_palette`], which takes the colours they use and then the rest
+  /// of their colours \
+  /// the other data, needing the most colours first, go in the first sub-palette of `sub_palettes`
+  /// their colours fit, `None` if there is none \
+  /// `needs` are the colours used by every data of the atlas, see [`Atlas::sprite_needs`]
+  pub(crate) fn place_sub_palettes(
+    &self,
+    needs: &[[Option<usize>; 3]],
+    data: &[usize],
+    sub_palettes: &mut [[Option<usize>; 3]; SPRITE_SUB_PALETTES],
+  ) -> Result<Vec<Option<usize>>, crate::Error> {
+    let mut placed = vec![None; data.len()];
+
+    for (i, j) in data.iter().enumerate() {
+      if let Some(s) = self.data[*j]
+        .sub_palette
+        .filter(|s| *s < SPRITE_SUB_PALETTES)
+      {
+        sub_palette_merge(&mut sub_palettes[s], &needs[*j]);
+        placed[i] = Some(s);
+      }
+    }
+    for (j, s) in data.iter().zip(placed.iter()) {
+      if let Some(s) = s {
+        let colors = self.get_colors(&self.data[*j])?;
+        sub_palette_merge(
+          &mut sub_palettes[*s],
+          &[0, 1, 2].map(|k| colors.get(k).copied()),
+        );
+      }
+    }
+
+    let mut rest: Vec<usize> = (0..data.len())
+      .filter(|i| self.data[data[*i]].sub_palette.is_none())
+      .collect();
+    rest.sort_by_key(|i| std::cmp::Reverse(needs[data[*i]].iter().flatten().count()));
+    for i in rest {
+      let need = &needs[data[i]];
+      placed[i] = sub_palettes
+        .iter()
+        .position(|sp| sub_palette_fits(sp, need));
+      if let Some(s) = placed[i] {
+        sub_palette_merge(&mut sub_palettes[s], need);
+      }
+    }
+
+    Ok(placed)
+  }
+
+  /// returns the groups of the atlas as metasprites, in order of their first data
+  ///
+  /// offsets are relative to the anchor of the group \
+  /// data keep their [`AtlasData::sub_palette`], the sub-palettes of the other data are found
+  /// from their colours, separately for every group \
+  /// data without a group are left out
+  pub fn get_metasprites(&self) -> Result<Vec<Metasprite>, crate::Error> {
+    let needs = self.sprite_needs()?;
+
+    let mut names: Vec<&str> = Vec::new();
+    for name in self.data.iter().filter_map(|d| d.group.as_deref()) {
+      if !names.contains(&name) {
+        names.push(name);
+      }
+    }
+
+    let mut metasprites = Vec::new();
+    for name in names {
+      let members: Vec<usize> = (0..self.data.len())
+        .filter(|j| self.data[*j].group.as_deref() == Some(name))
+        .collect();
+      let (ax, ay) = self.get_group_anchor(name).unwrap_or_default();
+
+      let mut sub_palettes = [[None; 3]; SPRITE_SUB_PALETTES];
+      let placed = self.place_sub_palettes(&needs, &members, &mut sub_palettes)?;
+
+      let mut entries = Vec::new();
+      for (j, sub_palette) in members.into_iter().zip(placed) {
+        let d = &self.data[j];
+        if d.transpose {
+          return Err(crate::Error::MetaspriteTransposeError(j));
+        }
+        let tile = u8::try_from(d.chr_index)
+          .map_err(|_| crate::Error::MetaspriteTileError(j, d.chr_index))?;
+
+        // an x offset of -128 would end the table
+        let (x, y) = (d.x as i64 - ax as i64, d.y as i64 - ay as i64);
+        let offset = match (i8::try_from(x), i8::try_from(y)) {
+          (Ok(x), Ok(y)) if x as u8 != METASPRITE_END => (x, y),
+          _ => return Err(crate::Error::MetaspriteOffsetError(j, x, y)),
+        };
+
+        let sub_palette = sub_palette.ok_or(crate::Error::MetaspriteSubPaletteError(j))?;
+
+        entries.push(MetaspriteEntry {
+          x: offset.0,
+          y: offset.1,
+          tile,
+          attribute: oam_attribute(d, sub_palette),
+        });
+      }
+
+      metasprites.push(Metasprite {
+        name: name.to_string(),
+        entries,
+        sub_palettes,
+      });
+    }
+
+    Ok(metasprites)
+  }
+
+  /// reads the metasprite table at `offset` into a new group of data, returns the size of the table
+  ///
+  /// see [`Atlas::add_metasprite`]
+  pub fn import_metasprite(
+    &mut self,
+    name: &str,
+    b: &[u8],
+    offset: usize,
+    layout: &MetaspriteLayout,
+    sub_palettes: &[[usize; 3]],
+    anchor: Option<(u32, u32)>,
+  ) -> Result<usize, crate::Error> {
+    let (sprites, size) = layout.read(b, offset)?;
+    self.add_metasprite(name, &sprites, sub_palettes, anchor)?;
+    Ok(size)
+  }
+
+  /// adds sprites as a new group of data
+  ///
+  /// sub-palettes give the colours 1 to 3 of the data \
+  /// the group is anchored at `anchor`, or else on the right of the other data of the atlas \
+  /// fails if the atlas already has a group named `name`
+  pub fn add_metasprite(
+    &mut self,
+    name: &str,
+    sprites: &[TableSprite],
+    sub_palettes: &[[usize; 3]],
+    anchor: Option<(u32, u32)>,
+  ) -> Result<(), crate::Error> {
+    if self.groups.contains_key(name) || self.data.iter().any(|d| d.group.as_deref() == Some(name))
+    {
+      return Err(crate::Error::MetaspriteGroupError(name.to_string()));
+    }
+
+    let (ax, ay) = match anchor {
+      Some((x, y)) => (x as i64, y as i64),
+      None => {
+        let right = self.data.iter().map(|d| d.x as i64 + 16).max().unwrap_or(0);
+        let min_x = sprites.iter().map(|s| s.x).min().unwrap_or(0);
+        let min_y = sprites.iter().map(|s| s.y).min().unwrap_or(0);
+        ((right - min_x).max(0), (-min_y).max(0))
+      }
+    };
+
+    let mut data = Vec::new();
+    for (i, s) in sprites.iter().enumerate() {
+      let (x, y) = (ax + s.x, ay + s.y);
+      let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+        return Err(crate::Error::MetaspritePositionError(i, x, y));
+      };
+      let colors = sub_palettes
+        .get(s.sub_palette)
+        .ok_or(crate::Error::AtlasPaletteIndexError(
+          s.sub_palette,
+          sub_palettes.len(),
+        ))?;
+
+      data.push(AtlasData {
+        chr_index: s.tile,
+        c0: colors[0],
+        c1: colors[1],
+        c2: colors[2],
+        x,
+        y,
+        flip_x: s.flip_x,
+        flip_y: s.flip_y,
+        behind_background: s.behind_background,
+        sub_palette: Some(s.sub_palette),
+        group: Some(name.to_string()),
+        ..Default::default()
+      });
+    }
+
+    self.data.extend(data);
+    self.groups.insert(
+      name.to_string(),
+      AtlasGroup {
+        x: ax as u32,
+        y: ay as u32,
+      },
+    );
+
+    Ok(())
+  }
+
+  /// exports the groups of the atlas as metasprite tables
+  ///
+  /// every sprite is written as x, y, tile and attribute bytes, and every table ends with
+  /// [`METASPRITE_END`] \
+  /// the sprite sub-palettes of every table are written in a comment, group names are turned into
+  /// identifiers, and two groups cannot give the same identifier
+  pub fn export_metasprites(&self, format: MetaspriteFormat) -> Result<String, crate::Error> {
+    let metasprites = self.get_metasprites()?;
+
+    let (comment, hex) = match format {
+      MetaspriteFormat::Ca65 | MetaspriteFormat::Asm6 => (";", "$"),
+      MetaspriteFormat::Neslib => ("//", "0x"),
+    };
+    let byte = |v: u8| format!("{}{:02X}", hex, v);
+
+    let names: Vec<&str> = metasprites.iter().map(|m| m.name.as_str()).collect();
+    let ids = identifiers(&names)?;
+
+    let mut out = String::new();
+    for (m, id) in metasprites.iter().zip(ids) {
+      let rows: Vec<String> = m
+        .entries
+        .iter()
+        .map(|e| {
+          [e.x as u8, e.y as u8, e.tile, e.attribute]
+            .map(byte)
+            .join(",")
+        })
+        .collect();
+
+      if !out.is_empty() {
+        writeln!(out).unwrap();
+      }
+      writeln!(out, "{} sprite sub-palettes", comment).unwrap();
+      for (s, sp) in m.sub_palettes.iter().enumerate() {
+        if sp.iter().all(|c| c.is_none()) {
+          continue;
+        }
+        let colors: Vec<String> = sp
+          .iter()
+          .map(|c| match c {
+            Some(c) => byte(*c as u8),
+            None => "--".to_string(),
+          })
+          .collect();
+        writeln!(out, "{} {}: {}", comment, s, colors.join(",")).unwrap();
+      }
+      match format {
+        MetaspriteFormat::Ca65 | MetaspriteFormat::Asm6 => {
+          let directive = match format {
+            MetaspriteFormat::Ca65 => ".byte",
+            _ => ".db",
+          };
+          if format == MetaspriteFormat::Ca65 {
+            writeln!(out, ".export {}", id).unwrap();
+          }
+          writeln!(out, "{}:", id).unwrap();
+          for row in rows {
+            writeln!(out, "  {} {}", directive, row).unwrap();
+          }
+          writeln!(out, "  {} {}", directive, byte(METASPRITE_END)).unwrap();
+        }
+        MetaspriteFormat::Neslib => {
+          writeln!(out, "const unsigned char {}[]={{", id).unwrap();
+          for row in rows {
+            writeln!(out, "  {},", row).unwrap();
+          }
+          writeln!(out, "  {}", byte(METASPRITE_END)).unwrap();
+          writeln!(out, "}};").unwrap();
+        }
+      }
+    }
+
+    Ok(out)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn layout_without_fields() {
+    let layout = MetaspriteLayout {
+      fields: Vec::new(),
+      ..Default::default()
+    };
+    assert!(matches!(
+      layout.read(&[0, 0, 0, 0], 0),
+      Err(crate::Error::MetaspriteFieldsError)
+    ));
+  }
+
+  #[test]
+  fn layout_too_many_sprites() {
+    let layout = MetaspriteLayout {
+      end: MetaspriteEnd::Fixed(usize::MAX),
+      ..Default::default()
+    };
+    assert!(matches!(
+      layout.read(&[0; 16], 0),
+      Err(crate::Error::MetaspriteCountError(usize::MAX))
+    ));
+  }
+
+  #[test]
+  fn identifier_clash() {
+    assert_eq!(identifiers(&["a-1", "2b"]).unwrap(), ["a_1", "_2b"]);
+    assert!(matches!(
+      identifiers(&["a_1", "b", "a-1"]),
+      Err(crate::Error::MetaspriteIdentifierError(a, b, id)) if a == "a_1" && b == "a-1" && id == "a_1"
+    ));
+  }
+
+  #[test]
+  fn layout_reads_neslib_table() {
+    let b = [
+      0xF8,
+      0xFC,
+      0x01,
+      0x41,
+      0x00,
+      0x00,
+      0x02,
+      0x00,
+      METASPRITE_END,
+    ];
+    let (sprites, size) = MetaspriteLayout::default().read(&b, 0).unwrap();
+    assert_eq!(size, b.len());
+    assert_eq!(sprites.len(), 2);
+    assert_eq!((sprites[0].x, sprites[0].y, sprites[0].tile), (-8, -4, 1));
+    assert_eq!(sprites[0].sub_palette, 1);
+    assert!(sprites[0].flip_x);
+  }
+}
@@ -0,0 +1,66 @@
+/// width of a nametable, in tiles
+pub const NAMETABLE_WIDTH: usize = 32;
+/// height of a nametable, in tiles
+pub const NAMETABLE_HEIGHT: usize = 30;
+
+/// a nametable, describes a full screen of background tiles
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Nametable {
+  /// tile indices, row by row
+  pub tiles: [u8; NAMETABLE_WIDTH * NAMETABLE_HEIGHT],
+  /// attribute table, one byte per 32x32 pixel area
+  pub attributes: [u8; 64],
+}
+
+impl Default for Nametable {
+  fn default() -> Self {
+    Self {
+      tiles: [0; NAMETABLE_WIDTH * NAMETABLE_HEIGHT],
+      attributes: [0; 64],
+    }
+  }
+}
+
+impl Nametable {
+  /// reads a nametable from its 1024 bytes (tiles followed by attributes)
+  pub fn from_bytes(b: &[u8]) -> Result<Self, crate::Error> {
+    if b.len() < 1024 {
+      return Err(crate::Error::NametableSizeError(b.len()));
+    }
+
+    let (tiles, attributes) = b[..1024].split_at(NAMETABLE_WIDTH * NAMETABLE_HEIGHT);
+
+    Ok(Self {
+      tiles: tiles.try_into().map_err(crate::Error::BytesError)?,
+      attributes: attributes.try_into().map_err(crate::Error::BytesError)?,
+    })
+  }
+
+  /// returns the 1024 bytes of the nametable
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut b = self.tiles.to_vec();
+    b.extend_from_slice(&self.attributes);
+    b
+  }
+
+  /// returns the tile index at a tile position
+  pub fn tile(&self, x: usize, y: usize) -> u8 {
+    self.tiles[y * NAMETABLE_WIDTH + x]
+  }
+
+  /// returns the sub-palette (0 to 3) of a tile position
+  pub fn sub_palette(&self, x: usize, y: usize) -> usize {
+    let attribute = self.attributes[(y / 4) * 8 + x / 4];
+    let shift = ((y % 4) / 2) * 4 + ((x % 4) / 2) * 2;
+    (attribute >> shift) as usize & 0x03
+  }
+
+  /// sets the sub-palette (0 to 3) of a tile position
+  ///
+  /// sub-palettes are shared by 2x2 tile areas
+  pub fn set_sub_palette(&mut self, x: usize, y: usize, sub_palette: usize) {
+    let attribute = &mut self.attributes[(y / 4) * 8 + x / 4];
+    let shift = ((y % 4) / 2) * 4 + ((x % 4) / 2) * 2;
+    *attribute = (*attribute & !(0x03 << shift)) | ((sub_palette as u8 & 0x03) << shift);
+  }
+}
@@ -0,0 +1,567 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+  atlas::{Atlas, AtlasBackground, AtlasNametable, AtlasSource},
+  compression::Compression,
+  lint::{SPRITE_SUB_PALETTES, sub_palette_merge},
+  metasprite::{AttributeLayout, TableSprite},
+  nametable::{NAMETABLE_HEIGHT, NAMETABLE_WIDTH, Nametable},
+  pal::PaletteFormat,
+  ppu::{PpuMask, PpuType},
+};
+
+/// number of metasprites of a bank
+pub const BANK_METASPRITES: usize = 256;
+/// number of sprites of a metasprite of a bank
+pub const BANK_SPRITES: usize = 64;
+/// y position of an unused sprite of a bank
+const EMPTY_SPRITE: u8 = 0xFF;
+
+/// a `.pal` palette of NES Screen Tool / NEXXT, in NES colour indices
+///
+/// the file is 16 bytes of background palette, optionally followed by 16 bytes of sprite palette
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct NesstPalette {
+  /// colour 0 of every sub-palette
+  pub backdrop: usize,
+  /// the four background sub-palettes (colors 1 to 3)
+  pub background: [[usize; 3]; 4],
+  /// the four sprite sub-palettes (colors 1 to 3), only in 32 byte files
+  pub sprites: Option<[[usize; 3]; 4]>,
+}
+
+impl NesstPalette {
+  /// reads a palette from 16 or 32 bytes, the copies of the backdrop are ignored
+  pub fn from_bytes(b: &[u8]) -> Result<Self, crate::Error> {
+    if b.len() < 16 {
+      return Err(crate::Error::NesstPaletteSizeError(b.len()));
+    }
+    let sub_palettes =
+      |b: &[u8]| [0, 1, 2, 3].map(|s| [1, 2, 3].map(|c| (b[s * 4 + c] & 0x3F) as usize));
+
+    Ok(Self {
+      backdrop: (b[0] & 0x3F) as usize,
+      background: sub_palettes(&b[..16]),
+      sprites: (b.len() >= 32).then(|| sub_palettes(&b[16..32])),
+    })
+  }
+
+  /// returns the 16 or 32 bytes of the palette
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut b = Vec::new();
+    for sub_palettes in std::iter::once(&self.background).chain(self.sprites.iter()) {
+      for sp in sub_palettes {
+        b.push(self.backdrop as u8);
+        b.extend(sp.iter().map(|c| *c as u8));
+      }
+    }
+    b
+  }
+
+  /// returns a nametable background using the background sub-palettes
+  pub fn to_background(&self, binary: String, start: u64, chr_offset: usize) -> AtlasNametable {
+    AtlasNametable {
+      binary,
+      start,
+      chr_offset,
+      backdrop: self.backdrop,
+      palettes: self.background,
+    }
+  }
+}
+
+/// reads a `.nam` file, raw or compressed with [`Compression::Rle`]
+///
+/// a raw file of 960 bytes only holds tile names, its attributes are all 0
+pub fn read_nam(b: &[u8]) -> Result<Nametable, crate::Error> {
+  match b.len() {
+    1024 => Nametable::from_bytes(b),
+    960 => {
+      let mut b = b.to_vec();
+      b.resize(1024, 0);
+      Nametable::from_bytes(&b)
+    }
+    _ => Nametable::from_bytes(&Compression::Rle.decompress(b, 1024)?.data),
+  }
+}
+
+/// a sprite of a metasprite bank
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct BankSprite {
+  /// x position, the grid being the origin of the metasprite
+  pub x: u8,
+  /// y position, the grid being the origin of the metasprite
+  pub y: u8,
+  /// tile number
+  pub tile: u8,
+  /// OAM attribute byte
+  pub attribute: u8,
+}
+
+/// a `.msb` metasprite bank
+///
+/// the file is the x and y of the grid, followed by 256 metasprites of 64 sprites stored as y, tile,
+/// attribute and x \
+/// unused sprites have a y of `$FF`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MetaspriteBank {
+  /// x of the origin of the metasprites
+  pub grid_x: u8,
+  /// y of the origin of the metasprites
+  pub grid_y: u8,
+  /// sprites of the 256 metasprites
+  pub metasprites: Vec<Vec<BankSprite>>,
+}
+
+impl Default for MetaspriteBank {
+  fn default() -> Self {
+    Self {
+      grid_x: 64,
+      grid_y: 64,
+      metasprites: vec![Vec::new(); BANK_METASPRITES],
+    }
+  }
+}
+
+impl MetaspriteBank {
+  /// reads a bank from its bytes, metasprites missing from a short file are empty
+  pub fn from_bytes(b: &[u8]) -> Result<Self, crate::Error> {
+    if b.len() < 2 {
+      return Err(crate::Error::NesstBankSizeError(b.len()));
+    }
+
+    let mut records = b[2..].chunks_exact(4);
+    let metasprites = (0..BANK_METASPRITES)
+      .map(|_| {
+        records
+          .by_ref()
+          .take(BANK_SPRITES)
+          .filter(|r| r[0] != EMPTY_SPRITE)
+          .map(|r| BankSprite {
+            y: r[0],
+            tile: r[1],
+            attribute: r[2],
+            x: r[3],
+          })
+          .collect()
+      })
+      .collect();
+
+    Ok(Self {
+      grid_x: b[0],
+      grid_y: b[1],
+      metasprites,
+    })
+  }
+
+  /// returns the bytes of the bank
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut b = vec![self.grid_x, self.grid_y];
+    for m in 0..BANK_METASPRITES {
+      let sprites = self.metasprites.get(m).map(|m| m.as_slice()).unwrap_or(&[]);
+      for s in 0..BANK_SPRITES {
+        match sprites.get(s) {
+          Some(s) => b.extend([s.y, s.tile, s.attribute, s.x]),
+          None => b.extend([EMPTY_SPRITE, 0, 0, 0]),
+        }
+      }
+    }
+    b
+  }
+
+  /// returns the sprites of a metasprite, relative to the grid
+  pub fn get_sprites(&self, m: usize) -> Vec<TableSprite> {
+    let attribute = AttributeLayout::default();
+    self.metasprites.get(m).map_or(Vec::new(), |sprites| {
+      sprites
+        .iter()
+        .map(|s| {
+          let mut sprite = TableSprite {
+            x: s.x as i64 - self.grid_x as i64,
+            y: s.y as i64 - self.grid_y as i64,
+            tile: s.tile as usize,
+            sub_palette: 0,
+            flip_x: false,
+            flip_y: false,
+            behind_background: false,
+          };
+          attribute.decode(s.attribute, &mut sprite);
+          sprite
+        })
+        .collect()
+    })
+  }
+
+  /// returns an atlas with a group per metasprite, named `metasprite_XX` after its number in the
+  /// bank
+  ///
+  /// sub-palettes give the colours 1 to 3 of the data, see [`Atlas::add_metasprite`]
+  pub fn to_atlas(
+    &self,
+    source: AtlasSource,
+    palette: String,
+    sub_palettes: &[[usize; 3]],
+  ) -> Result<Atlas, crate::Error> {
+    let mut atlas = Atlas {
+      source,
+      sources: BTreeMap::new(),
+      palette,
+      palette_format: PaletteFormat::default(),
+      ppu: PpuType::default(),
+      mask: PpuMask::default(),
+      data: Vec::new(),
+      background: None,
+      groups: BTreeMap::new(),
+      animations: BTreeMap::new(),
+    };
+
+    for m in 0..self.metasprites.len() {
+      let sprites = self.get_sprites(m);
+      if !sprites.is_empty() {
+        atlas.add_metasprite(&bank_name(m), &sprites, sub_palettes, None)?;
+      }
+    }
+
+    Ok(atlas)
+  }
+
+  /// creates a bank from the groups of an atlas, see [`Atlas::get_metasprites`]
+  ///
+  /// groups named `metasprite_XX` go back to their number, other groups fill the free numbers in
+  /// order
+  pub fn from_atlas(atlas: &Atlas, grid_x: u8, grid_y: u8) -> Result<Self, crate::Error> {
+    let metasprites = atlas.get_metasprites()?;
+    let mut bank = Self {
+      grid_x,
+      grid_y,
+      metasprites: vec![Vec::new(); BANK_METASPRITES],
+    };
+
+    let mut numbers: Vec<Option<usize>> = metasprites
+      .iter()
+      .map(|m| (0..BANK_METASPRITES).find(|n| bank_name(*n) == m.name.to_ascii_lowercase()))
+      .collect();
+    let free: Vec<usize> = (0..BANK_METASPRITES)
+      .filter(|n| !numbers.contains(&Some(*n)))
+      .collect();
+    let mut free = free.into_iter();
+    for n in numbers.iter_mut().filter(|n| n.is_none()) {
+      *n = Some(
+        free
+          .next()
+          .ok_or(crate::Error::NesstBankFullError(metasprites.len()))?,
+      );
+    }
+
+    for (m, n) in metasprites.iter().zip(numbers.into_iter().flatten()) {
+      if m.entries.len() > BANK_SPRITES {
+        return Err(crate::Error::NesstSpriteCountError(
+          m.name.clone(),
+          m.entries.len(),
+        ));
+      }
+      for e in &m.entries {
+        // positions are stored as bytes, and a y of $FF is an unused sprite
+        let (x, y) = (grid_x as i64 + e.x as i64, grid_y as i64 + e.y as i64);
+        let (Ok(bx), Ok(by)) = (u8::try_from(x), u8::try_from(y)) else {
+          return Err(crate::Error::NesstSpritePositionError(m.name.clone(), x, y));
+        };
+        if by == EMPTY_SPRITE {
+          return Err(crate::Error::NesstSpritePositionError(m.name.clone(), x, y));
+        }
+        bank.metasprites[n].push(BankSprite {
+          x: bx,
+          y: by,
+          tile: e.tile,
+          attribute: e.attribute,
+        });
+      }
+    }
+
+    Ok(bank)
+  }
+}
+
+/// name of the group of a metasprite of a bank
+fn bank_name(m: usize) -> String {
+  format!("metasprite_{:02x}", m)
+}
+
+/// a `.nss` session of NES Screen Tool or NEXXT
+///
+/// sessions are text, one `key=value` per line with the data written as hexadecimal bytes \
+/// the keys read and written are `CHRMain`, `NameTable` and `AttrTable` (a 32x30 screen),
+/// `Palette` (as in a `.pal` file), `MetaSprites` (the metasprites of a `.msb` file) and
+/// `VarSpriteGridX` / `VarSpriteGridY` (decimal), other keys are kept as is
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct NesstSession {
+  /// chr of the session
+  pub chr: Vec<u8>,
+  /// background screen
+  pub nametable: Option<Nametable>,
+  /// palette
+  pub palette: Option<NesstPalette>,
+  /// metasprite bank
+  pub metasprites: Option<MetaspriteBank>,
+  /// every other value, by key
+  pub other: BTreeMap<String, String>,
+}
+
+/// first line of a session
+const SESSION_HEADER: &str = "NSTs";
+
+impl NesstSession {
+  /// reads a session from its text
+  pub fn from_text(s: &str) -> Result<Self, crate::Error> {
+    let mut values: BTreeMap<String, String> = s
+      .lines()
+      .filter_map(|l| l.split_once('='))
+      .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+      .collect();
+    let mut bytes = |key: &str| -> Result<Option<Vec<u8>>, crate::Error> {
+      values.remove(key).map(|v| parse_hex(key, &v)).transpose()
+    };
+
+    let chr = bytes("CHRMain")?.unwrap_or_default();
+    let nametable = match (bytes("NameTable")?, bytes("AttrTable")?) {
+      (Some(mut tiles), attributes) => {
+        if tiles.len() != NAMETABLE_WIDTH * NAMETABLE_HEIGHT {
+          return Err(crate::Error::NesstSessionError("NameTable".to_string()));
+        }
+        tiles.extend(attributes.unwrap_or_else(|| vec![0; 64]));
+        Some(Nametable::from_bytes(&tiles)?)
+      }
+      (None, _) => None,
+    };
+    let palette = bytes("Palette")?
+      .map(|b| NesstPalette::from_bytes(&b))
+      .transpose()?;
+    let metasprites = bytes("MetaSprites")?;
+
+    let mut grid = |key: &str| -> Result<u8, crate::Error> {
+      match values.remove(key) {
+        Some(v) => v
+          .parse()
+          .map_err(|_| crate::Error::NesstSessionError(key.to_string())),
+        None => Ok(64),
+      }
+    };
+    let (grid_x, grid_y) = (grid("VarSpriteGridX")?, grid("VarSpriteGridY")?);
+    let metasprites = metasprites
+      .map(|mut b| {
+        b.splice(0..0, [grid_x, grid_y]);
+        MetaspriteBank::from_bytes(&b)
+      })
+      .transpose()?;
+
+    Ok(Self {
+      chr,
+      nametable,
+      palette,
+      metasprites,
+      other: values,
+    })
+  }
+
+  /// returns the text of the session
+  pub fn to_text(&self) -> String {
+    let hex = |b: &[u8]| {
+      b.iter()
+        .fold(String::new(), |s, v| s + &format!("{:02X}", v))
+    };
+
+    let mut out = String::new();
+    writeln!(out, "{}", SESSION_HEADER).unwrap();
+    let mut line = |key: &str, value: String| writeln!(out, "{}={}", key, value).unwrap();
+    line("CHRMain", hex(&self.chr));
+    if let Some(n) = &self.nametable {
+      line("NameTable", hex(&n.tiles));
+      line("AttrTable", hex(&n.attributes));
+    }
+    if let Some(p) = &self.palette {
+      line("Palette", hex(&p.to_bytes()));
+    }
+    if let Some(m) = &self.metasprites {
+      line("VarSpriteGridX", m.grid_x.to_string());
+      line("VarSpriteGridY", m.grid_y.to_string());
+      line("MetaSprites", hex(&m.to_bytes()[2..]));
+    }
+    for (k, v) in &self.other {
+      line(k, v.clone());
+    }
+
+    out
+  }
+
+  /// creates a session from the main source, nametable background, palettes and groups of an atlas
+  pub fn from_atlas(atlas: &Atlas, grid_x: u8, grid_y: u8) -> Result<Self, crate::Error> {
+    let nametable = match &atlas.background {
+      Some(AtlasBackground::Nametable(n)) => Some(n.get_nametable()?),
+      _ => None,
+    };
+    let metasprites = match atlas.data.iter().any(|d| d.group.is_some()) {
+      true => Some(MetaspriteBank::from_atlas(atlas, grid_x, grid_y)?),
+      false => None,
+    };
+
+    Ok(Self {
+      chr: atlas.source.get_pattern_table()?,
+      nametable,
+      palette: Some(atlas.get_nesst_palette()?),
+      metasprites,
+      other: BTreeMap::new(),
+    })
+  }
+}
+
+/// parses the hexadecimal bytes of a session value
+fn parse_hex(key: &str, v: &str) -> Result<Vec<u8>, crate::Error> {
+  let error = || crate::Error::NesstSessionError(key.to_string());
+  if !v.len().is_multiple_of(2) {
+    return Err(error());
+  }
+  (0..v.len())
+    .step_by(2)
+    .map(|i| {
+      v.get(i..i + 2)
+        .and_then(|b| u8::from_str_radix(b, 16).ok())
+        .ok_or_else(error)
+    })
+    .collect()
+}
+
+impl Atlas {
+  /// returns the palette of the atlas in the layout of a `.pal` file
+  ///
+  /// the background comes from a nametable background, the sprites from the sub-palettes of
+  /// [`Atlas::get_metasprites`] then of the data without a group, missing colours are the backdrop
+  pub fn get_nesst_palette(&self) -> Result<NesstPalette, crate::Error> {
+    let (backdrop, background) = match &self.background {
+      Some(AtlasBackground::Nametable(n)) => (n.backdrop, n.palettes),
+      Some(AtlasBackground::Backdrop(c)) => (*c, [[*c; 3]; 4]),
+      None => (0x0F, [[0x0F; 3]; 4]),
+    };
+
+    let mut sub_palettes = [[None; 3]; SPRITE_SUB_PALETTES];
+    for m in self.get_metasprites()? {
+      for (sp, m_sp) in sub_palettes.iter_mut().zip(m.sub_palettes.iter()) {
+        sub_palette_merge(sp, m_sp);
+      }
+    }
+    let ungrouped: Vec<usize> = (0..self.data.len())
+      .filter(|j| self.data[*j].group.is_none())
+      .collect();
+    self.place_sub_palettes(&self.sprite_needs()?, &ungrouped, &mut sub_palettes)?;
+    let sprites = sub_palettes.map(|sp| sp.map(|c| c.unwrap_or(backdrop)));
+
+    Ok(NesstPalette {
+      backdrop,
+      background,
+      sprites: Some(sprites),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::codec::TileFormat;
+
+  /// a chr of 256 tiles using every colour, written to a temporary file
+  fn chr_source(name: &str) -> (AtlasSource, Vec<u8>) {
+    let chr: Vec<u8> = (0..256)
+      .flat_map(|_| [[0x55; 8], [0x33; 8]].concat())
+      .collect();
+    let path = std::env::temp_dir().join(format!("chr-reatlas-{}.chr", name));
+    std::fs::write(&path, &chr).unwrap();
+
+    let source = AtlasSource {
+      binary: path.to_string_lossy().to_string(),
+      start: 0,
+      length: chr.len(),
+      banks: Vec::new(),
+      format: TileFormat::Nes,
+      fds: None,
+      compression: None,
+    };
+    (source, chr)
+  }
+
+  /// a bank whose sprites use sub-palettes 1, 0, 2 and 3, with flips and priority
+  fn bank() -> MetaspriteBank {
+    let mut bank = MetaspriteBank::default();
+    bank.metasprites[0] = vec![
+      BankSprite {
+        x: 56,
+        y: 60,
+        tile: 1,
+        attribute: 0x41,
+      },
+      BankSprite {
+        x: 64,
+        y: 60,
+        tile: 2,
+        attribute: 0x00,
+      },
+      BankSprite {
+        x: 56,
+        y: 68,
+        tile: 3,
+        attribute: 0x82,
+      },
+    ];
+    bank.metasprites[5] = vec![BankSprite {
+      x: 64,
+      y: 64,
+      tile: 4,
+      attribute: 0x23,
+    }];
+    bank
+  }
+
+  fn palette() -> NesstPalette {
+    NesstPalette {
+      backdrop: 0x0F,
+      background: [[0x0F; 3]; 4],
+      sprites: Some([
+        [0x16, 0x27, 0x18],
+        [0x01, 0x02, 0x03],
+        [0x21, 0x22, 0x23],
+        [0x06, 0x16, 0x26],
+      ]),
+    }
+  }
+
+  #[test]
+  fn msb_round_trip() {
+    let (source, _) = chr_source("msb");
+    let (bank, palette) = (bank(), palette());
+
+    let atlas = bank
+      .to_atlas(source, String::new(), &palette.sprites.unwrap())
+      .unwrap();
+    let exported = MetaspriteBank::from_atlas(&atlas, bank.grid_x, bank.grid_y).unwrap();
+    assert_eq!(exported.to_bytes(), bank.to_bytes());
+    assert_eq!(atlas.get_nesst_palette().unwrap(), palette);
+  }
+
+  #[test]
+  fn nss_round_trip() {
+    let (source, chr) = chr_source("nss");
+    let session = NesstSession {
+      chr,
+      nametable: None,
+      palette: Some(palette()),
+      metasprites: Some(bank()),
+      other: BTreeMap::new(),
+    };
+    let session = NesstSession::from_text(&session.to_text()).unwrap();
+
+    let bank = session.metasprites.as_ref().unwrap();
+    let atlas = bank
+      .to_atlas(source, String::new(), &palette().sprites.unwrap())
+      .unwrap();
+    let exported = NesstSession::from_atlas(&atlas, bank.grid_x, bank.grid_y).unwrap();
+    assert_eq!(exported, session);
+  }
+}
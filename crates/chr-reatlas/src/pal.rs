@@ -1,3 +1,8 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::render::greyscale_colors;
+
 /// a palette for a chr
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub struct ChrPalette {
@@ -21,6 +26,56 @@ impl Default for ChrPalette {
   }
 }
 
+/// a palette for a tile of any format
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TilePalette {
+  /// background colour, used by pixel value 0
+  pub cbg: Option<[u8; 3]>,
+  /// colours of pixel values 1 and up
+  pub colors: Vec<[u8; 3]>,
+}
+
+impl TilePalette {
+  /// shades of grey for every pixel value of a tile format, from dark to white
+  pub fn greyscale(bits_per_pixel: usize) -> Self {
+    Self {
+      cbg: None,
+      colors: greyscale_colors(bits_per_pixel),
+    }
+  }
+}
+
+impl From<ChrPalette> for TilePalette {
+  fn from(p: ChrPalette) -> Self {
+    Self {
+      cbg: p.cbg,
+      colors: vec![p.c0, p.c1, p.c2],
+    }
+  }
+}
+
+/// format of a palette file
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum PaletteFormat {
+  /// .pal file, 3 bytes (r, g, b) per colour
+  #[default]
+  Pal,
+  /// Genesis / Mega Drive CRAM dump, one big endian word (`0000BBB0GGG0RRR0`) per colour
+  GenesisCram,
+}
+
+impl PaletteFormat {
+  /// reads a palette of this format from its bytes
+  pub fn read(&self, b: Vec<u8>) -> Result<Vec<[u8; 3]>, crate::Error> {
+    match self {
+      PaletteFormat::Pal => read_palette_from_bytes(b),
+      PaletteFormat::GenesisCram => read_genesis_palette_from_bytes(b),
+    }
+  }
+}
+
 /// reads a palette from its bytes
 pub fn read_palette_from_bytes(b: Vec<u8>) -> Result<Vec<[u8; 3]>, crate::Error> {
   let slices = b.chunks(3);
@@ -32,3 +87,29 @@ pub fn read_palette_from_bytes(b: Vec<u8>) -> Result<Vec<[u8; 3]>, crate::Error>
 
   Ok(pal)
 }
+
+/// reads a Genesis / Mega Drive palette (CRAM dump) from its bytes
+pub fn read_genesis_palette_from_bytes(b: Vec<u8>) -> Result<Vec<[u8; 3]>, crate::Error> {
+  let slices = b.chunks(2);
+  let mut pal = Vec::new();
+
+  for s in slices {
+    let word = u16::from_be_bytes(s.try_into().map_err(crate::Error::BytesError)?);
+    let channel = |shift: u16| (((word >> shift) & 0x07) * 255 / 7) as u8;
+    pal.push([channel(1), channel(5), channel(9)]);
+  }
+
+  Ok(pal)
+}
+
+/// returns the index of the closest colour of a palette
+pub fn nearest_color(pal: &[[u8; 3]], c: [u8; 3]) -> usize {
+  let distance = |p: &[u8; 3]| {
+    (0..3)
+      .map(|i| (p[i] as i32 - c[i] as i32).pow(2))
+      .sum::<i32>()
+  };
+  (0..pal.len())
+    .min_by_key(|i| distance(&pal[*i]))
+    .unwrap_or(0)
+}
@@ -0,0 +1,207 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// ppu revision the graphics are displayed with
+///
+/// rgb ppus (arcade Vs. System and PlayChoice boards) have a fixed palette built into the chip,
+/// so they do not use a palette file
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum PpuType {
+  /// ntsc ppu, colours come from the palette file
+  #[default]
+  Rp2c02,
+  /// read the ppu from the NES 2.0 header of the binary, falls back to [`PpuType::Rp2c02`]
+  Header,
+  /// rgb ppu (PlayChoice, some Vs. System boards)
+  Rp2c03,
+  /// Vs. System rgb ppu with scrambled palette, variant 0001
+  Rp2c04_0001,
+  /// Vs. System rgb ppu with scrambled palette, variant 0002
+  Rp2c04_0002,
+  /// Vs. System rgb ppu with scrambled palette, variant 0003
+  Rp2c04_0003,
+  /// Vs. System rgb ppu with scrambled palette, variant 0004
+  Rp2c04_0004,
+  /// Vs. System rgb ppu, same palette as the 2C03
+  Rp2c05,
+}
+
+/// 2C03 palette, one octal digit per channel (r, g, b), from 0 to 7
+const RGB_PPU_PALETTE: [u16; 64] = [
+  0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000,
+  0o000, 0o000, 0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053,
+  0o044, 0o000, 0o000, 0o000, 0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360,
+  0o070, 0o276, 0o077, 0o000, 0o000, 0o000, 0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772,
+  0o773, 0o572, 0o473, 0o276, 0o467, 0o000, 0o000, 0o000,
+];
+
+/// 2C04 palettes, one octal digit per channel (r, g, b), from 0 to 7
+///
+/// the four variants share the same colours in a different order, some of which the 2C03 lacks
+const RP2C04_PALETTES: [[u16; 64]; 4] = [
+  [
+    0o755, 0o637, 0o700, 0o447, 0o044, 0o120, 0o222, 0o704, 0o777, 0o333, 0o750, 0o503, 0o403,
+    0o660, 0o320, 0o777, 0o357, 0o653, 0o310, 0o360, 0o467, 0o657, 0o764, 0o027, 0o760, 0o276,
+    0o000, 0o200, 0o666, 0o444, 0o707, 0o014, 0o003, 0o567, 0o757, 0o070, 0o077, 0o022, 0o053,
+    0o507, 0o000, 0o420, 0o747, 0o510, 0o407, 0o006, 0o740, 0o000, 0o000, 0o140, 0o555, 0o031,
+    0o572, 0o326, 0o772, 0o630, 0o020, 0o036, 0o040, 0o111, 0o773, 0o737, 0o430, 0o473,
+  ],
+  [
+    0o000, 0o750, 0o430, 0o572, 0o473, 0o737, 0o044, 0o567, 0o700, 0o407, 0o773, 0o747, 0o777,
+    0o637, 0o467, 0o040, 0o020, 0o357, 0o510, 0o666, 0o053, 0o360, 0o200, 0o447, 0o222, 0o707,
+    0o003, 0o276, 0o657, 0o320, 0o000, 0o326, 0o403, 0o764, 0o740, 0o757, 0o036, 0o310, 0o555,
+    0o006, 0o507, 0o760, 0o333, 0o120, 0o027, 0o000, 0o660, 0o777, 0o653, 0o111, 0o070, 0o630,
+    0o022, 0o014, 0o704, 0o140, 0o000, 0o077, 0o420, 0o772, 0o755, 0o503, 0o031, 0o444,
+  ],
+  [
+    0o507, 0o737, 0o473, 0o555, 0o040, 0o777, 0o567, 0o120, 0o014, 0o000, 0o764, 0o320, 0o704,
+    0o666, 0o653, 0o467, 0o447, 0o044, 0o503, 0o027, 0o140, 0o430, 0o630, 0o053, 0o333, 0o326,
+    0o000, 0o006, 0o700, 0o510, 0o747, 0o755, 0o637, 0o020, 0o003, 0o772, 0o111, 0o750, 0o740,
+    0o777, 0o360, 0o403, 0o357, 0o707, 0o036, 0o444, 0o000, 0o310, 0o077, 0o200, 0o572, 0o757,
+    0o420, 0o070, 0o660, 0o222, 0o031, 0o000, 0o657, 0o773, 0o407, 0o276, 0o760, 0o022,
+  ],
+  [
+    0o430, 0o326, 0o044, 0o660, 0o000, 0o755, 0o014, 0o630, 0o555, 0o310, 0o070, 0o003, 0o764,
+    0o772, 0o040, 0o572, 0o737, 0o200, 0o027, 0o747, 0o000, 0o222, 0o510, 0o740, 0o653, 0o053,
+    0o447, 0o140, 0o403, 0o000, 0o473, 0o357, 0o503, 0o031, 0o420, 0o006, 0o407, 0o507, 0o333,
+    0o704, 0o022, 0o666, 0o036, 0o020, 0o111, 0o773, 0o444, 0o707, 0o757, 0o777, 0o320, 0o700,
+    0o760, 0o276, 0o777, 0o467, 0o000, 0o750, 0o637, 0o567, 0o360, 0o657, 0o077, 0o120,
+  ],
+];
+
+fn rgb_ppu_color(c: u16) -> [u8; 3] {
+  let channel = |v: u16| ((v & 0o7) * 255 / 7) as u8;
+  [channel(c >> 6), channel(c >> 3), channel(c)]
+}
+
+impl PpuType {
+  /// detects the ppu from a NES 2.0 header
+  ///
+  /// returns `None` if the header is not a NES 2.0 header, or if it does not describe a known ppu
+  pub fn from_nes_header(header: &[u8]) -> Option<Self> {
+    if header.len() < 16 || &header[0..4] != b"NES\x1A" || header[7] & 0x0C != 0x08 {
+      return None;
+    }
+
+    match header[7] & 0x03 {
+      // nes / famicom
+      0 => Some(Self::Rp2c02),
+      // Vs. System, ppu type in the lower nibble of byte 13
+      1 => match header[13] & 0x0F {
+        0x0 | 0x1 | 0x6 | 0x7 => Some(Self::Rp2c03),
+        0x2 => Some(Self::Rp2c04_0001),
+        0x3 => Some(Self::Rp2c04_0002),
+        0x4 => Some(Self::Rp2c04_0003),
+        0x5 => Some(Self::Rp2c04_0004),
+        0x8..=0xC => Some(Self::Rp2c05),
+        _ => None,
+      },
+      // PlayChoice
+      2 => Some(Self::Rp2c03),
+      _ => None,
+    }
+  }
+
+  /// whether this ppu outputs rgb directly
+  pub fn is_rgb(&self) -> bool {
+    !matches!(self, Self::Rp2c02 | Self::Header)
+  }
+
+  /// returns the 64 colours built into an rgb ppu, indexed like a palette file
+  ///
+  /// returns `None` for the 2C02, which uses a palette file
+  pub fn builtin_palette(&self) -> Option<Vec<[u8; 3]>> {
+    let palette = match self {
+      Self::Rp2c02 | Self::Header => return None,
+      Self::Rp2c03 | Self::Rp2c05 => &RGB_PPU_PALETTE,
+      Self::Rp2c04_0001 => &RP2C04_PALETTES[0],
+      Self::Rp2c04_0002 => &RP2C04_PALETTES[1],
+      Self::Rp2c04_0003 => &RP2C04_PALETTES[2],
+      Self::Rp2c04_0004 => &RP2C04_PALETTES[3],
+    };
+
+    Some(palette.iter().map(|c| rgb_ppu_color(*c)).collect())
+  }
+
+  /// applies PPUMASK effects to a palette
+  ///
+  /// on the 2C02, palettes of 512 colours are expected to contain every emphasis combination,
+  /// smaller palettes are attenuated instead \
+  /// rgb ppus drive emphasized channels to full intensity
+  pub fn apply_mask(&self, pal: &[[u8; 3]], mask: PpuMask) -> Vec<[u8; 3]> {
+    if mask == PpuMask::default() {
+      return pal.to_vec();
+    }
+
+    let emphasis = [mask.emphasis_red, mask.emphasis_green, mask.emphasis_blue];
+
+    (0..pal.len())
+      .map(|i| {
+        let index = match mask.greyscale {
+          true => i & !0x0F,
+          false => i,
+        };
+
+        if self.is_rgb() {
+          let mut c = pal[index];
+          for (channel, on) in emphasis.iter().enumerate() {
+            if *on {
+              c[channel] = 255;
+            }
+          }
+          c
+        } else if pal.len() >= 512 {
+          *pal.get(index | mask.emphasis() << 6).unwrap_or(&pal[index])
+        } else {
+          // emphasized channels are kept and the others darkened, all three darken everything
+          let mut c = pal[index];
+          let all = emphasis.iter().all(|e| *e);
+          if mask.emphasis() != 0 {
+            for (channel, on) in emphasis.iter().enumerate() {
+              if !*on || all {
+                c[channel] = (c[channel] as f32 * 0.816) as u8;
+              }
+            }
+          }
+          c
+        }
+      })
+      .collect()
+  }
+}
+
+/// colour effects of the PPUMASK register
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct PpuMask {
+  /// greyscale, only keeps the luminance bits of palette indices (`& 0x30`)
+  pub greyscale: bool,
+  /// emphasize red
+  pub emphasis_red: bool,
+  /// emphasize green
+  pub emphasis_green: bool,
+  /// emphasize blue
+  pub emphasis_blue: bool,
+}
+
+impl PpuMask {
+  /// reads the colour effects from a PPUMASK value
+  pub fn from_register(value: u8) -> Self {
+    Self {
+      greyscale: value & 0x01 != 0,
+      emphasis_red: value & 0x20 != 0,
+      emphasis_green: value & 0x40 != 0,
+      emphasis_blue: value & 0x80 != 0,
+    }
+  }
+
+  /// returns the emphasis bits, red being the lowest bit
+  pub fn emphasis(&self) -> usize {
+    self.emphasis_red as usize
+      | (self.emphasis_green as usize) << 1
+      | (self.emphasis_blue as usize) << 2
+  }
+}
@@ -3,8 +3,9 @@ use std::num::ParseIntError;
 use image::{Rgba, RgbaImage, codecs::png::PngEncoder};
 
 use crate::{
-  chr::{ChrPixelPattern, read_bytes},
-  pal::ChrPalette,
+  chr::{ChrPixelPattern, read_bytes, to_tile_pattern},
+  codec::TilePattern,
+  pal::{ChrPalette, TilePalette},
 };
 
 pub(crate) fn append_pattern_on_image(
@@ -14,29 +15,48 @@ pub(crate) fn append_pattern_on_image(
   sy: u32,
   pal: ChrPalette,
 ) {
-  for x in 0..8usize {
-    for y in 0..8usize {
-      let v = pat[y][x].value();
-      if v != 0 || pal.cbg.is_some() {
-        image.put_pixel(
-          x as u32 + sx,
-          y as u32 + sy,
-          match v {
-            0 => match pal.cbg {
-              None => panic!(),
-              Some(c) => Rgba([c[0], c[1], c[2], 255]),
-            },
-            1 => Rgba([pal.c0[0], pal.c0[1], pal.c0[2], 255]),
-            2 => Rgba([pal.c1[0], pal.c1[1], pal.c1[2], 255]),
-            3 => Rgba([pal.c2[0], pal.c2[1], pal.c2[2], 255]),
-            _ => panic!(),
-          },
-        );
-      }
+  append_tile_on_image(
+    image,
+    &to_tile_pattern(pat),
+    sx,
+    sy,
+    &[pal.c0, pal.c1, pal.c2],
+    pal.cbg,
+  );
+}
+
+/// draws a tile, pixel value `v` uses `colors[v - 1]`, and pixel value 0 uses `cbg`
+pub(crate) fn append_tile_on_image(
+  image: &mut RgbaImage,
+  pat: &TilePattern,
+  sx: u32,
+  sy: u32,
+  colors: &[[u8; 3]],
+  cbg: Option<[u8; 3]>,
+) {
+  for (y, row) in pat.iter().enumerate() {
+    for (x, v) in row.iter().enumerate() {
+      let c = match (*v, cbg) {
+        (0, None) => continue,
+        (0, Some(c)) => c,
+        (v, _) => colors[v as usize - 1],
+      };
+      image.put_pixel(x as u32 + sx, y as u32 + sy, Rgba([c[0], c[1], c[2], 255]));
     }
   }
 }
 
+/// returns greys for every non-zero pixel value of a tile format, from dark to white
+pub fn greyscale_colors(bits_per_pixel: usize) -> Vec<[u8; 3]> {
+  let n = (1usize << bits_per_pixel) - 1;
+  (1..=n)
+    .map(|i| {
+      let v = (i * 255 / n) as u8;
+      [v, v, v]
+    })
+    .collect()
+}
+
 fn get_pattern(pat: ChrPixelPattern, pal: ChrPalette) -> Result<RgbaImage, crate::Error> {
   let mut img = RgbaImage::new(8, 8);
 
@@ -106,6 +126,35 @@ pub fn get_patterns_as_png_bytes(
   Ok(bytes)
 }
 
+/// gets a list of tiles as bytes of an image, in shades of grey
+///
+/// the number of tiles per line is 16
+pub fn get_tiles_as_png_bytes(
+  tiles: Vec<TilePattern>,
+  bits_per_pixel: usize,
+) -> Result<Vec<u8>, crate::Error> {
+  const TILES_PER_LINE: u32 = 16;
+  let img_width = TILES_PER_LINE * 8;
+  let img_height = (tiles.len() as u32).div_ceil(TILES_PER_LINE) * 8;
+
+  let mut img = RgbaImage::new(img_width, img_height);
+  let colors = greyscale_colors(bits_per_pixel);
+
+  for (i, tile) in tiles.iter().enumerate() {
+    let y = (i as u32).div_euclid(TILES_PER_LINE);
+    let x = (i as u32).rem_euclid(TILES_PER_LINE);
+    append_tile_on_image(&mut img, tile, x * 8, y * 8, &colors, None);
+  }
+
+  let mut bytes = Vec::new();
+  let encoder = PngEncoder::new(&mut bytes);
+  img
+    .write_with_encoder(encoder)
+    .map_err(crate::Error::ImageError)?;
+
+  Ok(bytes)
+}
+
 /// renders a list of patterns and graduates them in hexadecimal
 ///
 /// the number of patterns per line is 16
@@ -114,9 +163,28 @@ pub fn render_patterns_with_graduations(
   pats: Vec<ChrPixelPattern>,
   pals: Vec<ChrPalette>,
 ) -> Result<(), crate::Error> {
-  const PATS_PER_LINE: u32 = 16;
-  const LEFT_TILES_WIDTH: u32 = 4;
+  render_tiles_with_graduations(
+    path,
+    pats.into_iter().map(to_tile_pattern).collect(),
+    pals.into_iter().map(TilePalette::from).collect(),
+  )
+}
 
+const GRADUATED_PATS_PER_LINE: u32 = 16;
+const GRADUATED_LEFT_TILES_WIDTH: u32 = 4;
+
+/// position of the top left pixel of a tile in an image made by [`get_tiles_with_graduations`]
+pub(crate) fn graduated_tile_position(i: usize) -> (u32, u32) {
+  let y = (i as u32).div_euclid(GRADUATED_PATS_PER_LINE);
+  let x = (i as u32).rem_euclid(GRADUATED_PATS_PER_LINE);
+  ((x + GRADUATED_LEFT_TILES_WIDTH) * 8, (y + 1) * 8)
+}
+
+/// draws a list of tiles of any format, graduated in hexadecimal
+pub(crate) fn get_tiles_with_graduations(
+  pats: &[TilePattern],
+  pals: &[TilePalette],
+) -> Result<RgbaImage, crate::Error> {
   const LETTERS: &[u8] = include_bytes!("text.chr");
   const LETTERS_PALETTE: ChrPalette = ChrPalette {
     cbg: None,
@@ -127,8 +195,8 @@ pub fn render_patterns_with_graduations(
 
   let text_chrs = read_bytes(LETTERS.to_vec())?;
 
-  let img_width = (PATS_PER_LINE + LEFT_TILES_WIDTH) * 8;
-  let img_height = (pats.len() as u32).div_ceil(PATS_PER_LINE) * 8 + 8;
+  let img_width = (GRADUATED_PATS_PER_LINE + GRADUATED_LEFT_TILES_WIDTH) * 8;
+  let img_height = (pats.len() as u32).div_ceil(GRADUATED_PATS_PER_LINE) * 8 + 8;
 
   let mut img = RgbaImage::new(img_width, img_height);
 
@@ -136,15 +204,15 @@ pub fn render_patterns_with_graduations(
     append_pattern_on_image(
       &mut img,
       *t,
-      (x as u32 + LEFT_TILES_WIDTH) * 8,
+      (x as u32 + GRADUATED_LEFT_TILES_WIDTH) * 8,
       0,
       LETTERS_PALETTE,
     );
   }
 
   for (i, pat) in pats.iter().enumerate() {
-    let y = (i as u32).div_euclid(PATS_PER_LINE);
-    let x = (i as u32).rem_euclid(PATS_PER_LINE);
+    let y = (i as u32).div_euclid(GRADUATED_PATS_PER_LINE);
+    let x = (i as u32).rem_euclid(GRADUATED_PATS_PER_LINE);
 
     if x == 0 {
       let formatted: Result<Vec<usize>, ParseIntError> = format!("{:X}", y)
@@ -158,23 +226,37 @@ pub fn render_patterns_with_graduations(
         append_pattern_on_image(
           &mut img,
           text_chrs[*i],
-          (LEFT_TILES_WIDTH - 1 - x as u32) * 8,
+          (GRADUATED_LEFT_TILES_WIDTH - 1 - x as u32) * 8,
           (y + 1) * 8,
           LETTERS_PALETTE,
         );
       }
     }
 
-    append_pattern_on_image(
+    append_tile_on_image(
       &mut img,
-      *pat,
-      (x + LEFT_TILES_WIDTH) * 8,
+      pat,
+      (x + GRADUATED_LEFT_TILES_WIDTH) * 8,
       (y + 1) * 8,
-      pals[i],
+      &pals[i].colors,
+      pals[i].cbg,
     );
   }
 
-  img.save(path).map_err(crate::Error::ImageError)?;
+  Ok(img)
+}
+
+/// renders a list of tiles of any format and graduates them in hexadecimal
+///
+/// the number of tiles per line is 16
+pub fn render_tiles_with_graduations(
+  path: String,
+  pats: Vec<TilePattern>,
+  pals: Vec<TilePalette>,
+) -> Result<(), crate::Error> {
+  get_tiles_with_graduations(&pats, &pals)?
+    .save(path)
+    .map_err(crate::Error::ImageError)?;
 
   Ok(())
 }
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+  atlas::{Atlas, AtlasBackground, AtlasSource},
+  codec::{TilePattern, write_tiles},
+  find::TileTransform,
+};
+
+/// options of the atlas repacker
+#[derive(Clone, Copy, Debug)]
+pub struct RepackOptions {
+  /// reuse tiles that are flips of other tiles
+  pub flips: bool,
+}
+
+impl Default for RepackOptions {
+  fn default() -> Self {
+    Self { flips: true }
+  }
+}
+
+/// an atlas using a bank of only the tiles it needs
+#[derive(Clone, Debug)]
+pub struct RepackedAtlas {
+  /// atlas reading its tiles from the packed bank
+  pub atlas: Atlas,
+  /// tiles of the packed bank
+  pub tiles: Vec<TilePattern>,
+  /// encoded packed bank, in the format of the main source
+  pub chr: Vec<u8>,
+  /// source and index of the original tile of every packed tile
+  pub origins: Vec<(Option<String>, usize)>,
+}
+
+impl Atlas {
+  /// packs the tiles used by the data of this atlas into a new bank, read from `binary`
+  ///
+  /// identical tiles are kept once, and the indices and flips of the data are rewritten to point
+  /// into the packed bank \
+  /// a nametable background is replaced by its backdrop colour, its tiles are not packed, and a ppu
+  /// read from the header of the binary is written in the atlas \
+  /// every data must read a source in the tile format of the main source
+  pub fn repack(
+    &self,
+    binary: String,
+    options: &RepackOptions,
+  ) -> Result<RepackedAtlas, crate::Error> {
+    let chrs = self.get_all_chrs()?;
+    let transforms: Vec<TileTransform> = TileTransform::ALL
+      .into_iter()
+      .filter(|t| !t.transpose && (options.flips || *t == TileTransform::default()))
+      .collect();
+
+    let mut tiles: Vec<TilePattern> = Vec::new();
+    let mut origins = Vec::new();
+    // packed index of every known tile, and the flips that give it from the packed tile
+    let mut known: HashMap<TilePattern, (usize, TileTransform)> = HashMap::new();
+    let mut data = Vec::new();
+
+    for (i, d) in self.data.iter().enumerate() {
+      // the packed bank is encoded in the format of the main source
+      if self.get_source(d.source.as_deref())?.format != self.source.format {
+        return Err(crate::Error::RepackFormatError(
+          i,
+          d.source.clone().unwrap_or_default(),
+        ));
+      }
+
+      let tile = chrs.get(d)?;
+      let (chr_index, flips) = match known.get(&tile) {
+        Some(k) => *k,
+        None => {
+          tiles.push(tile);
+          origins.push((d.source.clone(), d.chr_index));
+          let index = tiles.len() - 1;
+          for t in &transforms {
+            let mut variant = tile;
+            t.apply(&mut variant);
+            known.entry(variant).or_insert((index, *t));
+          }
+          (index, TileTransform::default())
+        }
+      };
+
+      // flips happen before the transposition of the data, which swaps their axes
+      let mut d = d.clone();
+      let (flip_x, flip_y) = match d.transpose {
+        true => (flips.flip_y, flips.flip_x),
+        false => (flips.flip_x, flips.flip_y),
+      };
+      d.source = None;
+      d.chr_index = chr_index;
+      d.flip_x ^= flip_x;
+      d.flip_y ^= flip_y;
+      data.push(d);
+    }
+
+    let chr = write_tiles(&tiles, &*self.source.format.codec());
+    let mut atlas = self.clone();
+    // the packed bank has no header to read the ppu from
+    atlas.ppu = self.get_ppu()?;
+    atlas.source = AtlasSource {
+      binary,
+      start: 0,
+      length: chr.len(),
+      banks: Vec::new(),
+      format: self.source.format,
+      fds: None,
+      compression: None,
+    };
+    atlas.sources = BTreeMap::new();
+    atlas.data = data;
+    if let Some(AtlasBackground::Nametable(n)) = &atlas.background {
+      atlas.background = Some(AtlasBackground::Backdrop(n.backdrop));
+    }
+
+    Ok(RepackedAtlas {
+      atlas,
+      tiles,
+      chr,
+      origins,
+    })
+  }
+}
@@ -0,0 +1,233 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::codec::{TileFormat, TilePattern};
+
+/// options of the chr scanner
+#[derive(Clone, Copy, Debug)]
+pub struct ScanOptions {
+  /// format of the tiles to look for, the scanner walks the binary one tile at a time
+  pub format: TileFormat,
+  /// number of tiles scored together
+  pub window: usize,
+  /// minimum confidence of a window to be part of a candidate
+  pub threshold: f32,
+}
+
+impl Default for ScanOptions {
+  fn default() -> Self {
+    Self {
+      format: TileFormat::Nes,
+      window: 16,
+      threshold: 0.6,
+    }
+  }
+}
+
+/// a region of a binary that looks like chr data
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScanCandidate {
+  /// address of the region
+  pub start: usize,
+  /// size of the region, in bytes
+  pub length: usize,
+  /// mean confidence of the windows of the region, from 0 to 1
+  pub confidence: f32,
+}
+
+/// statistics of a single tile
+#[derive(Clone, Copy, Debug)]
+struct TileStats {
+  /// fraction of pixels where the two lowest bitplanes agree
+  plane_agreement: f32,
+  /// fraction of pixels equal to their neighbour above or on the left
+  similarity: f32,
+  /// whether every pixel has the same value
+  blank: bool,
+}
+
+impl TileStats {
+  fn new(t: &TilePattern, bits_per_pixel: usize) -> Self {
+    let pixels = t.iter().flatten();
+
+    let plane_agreement = match bits_per_pixel {
+      1 => 1.,
+      _ => pixels.clone().filter(|v| *v & 1 == (*v >> 1) & 1).count() as f32 / 64.,
+    };
+    let vertical = t
+      .windows(2)
+      .flat_map(|rows| rows[0].iter().zip(rows[1].iter()))
+      .filter(|(a, b)| a == b)
+      .count();
+    let horizontal = t
+      .iter()
+      .flat_map(|row| row.windows(2))
+      .filter(|p| p[0] == p[1])
+      .count();
+    let similarity = (vertical + horizontal) as f32 / 112.;
+    let blank = pixels.clone().all(|v| *v == t[0][0]);
+
+    Self {
+      plane_agreement,
+      similarity,
+      blank,
+    }
+  }
+}
+
+/// shannon entropy of a byte histogram, in bits per byte
+fn entropy(histogram: &[usize; 256]) -> f32 {
+  let total: usize = histogram.iter().sum();
+  histogram
+    .iter()
+    .filter(|n| **n != 0)
+    .map(|n| {
+      let p = *n as f32 / total as f32;
+      -p * p.log2()
+    })
+    .sum()
+}
+
+/// maps `v` from `[low, high]` to `[0, 1]`, clamping
+fn ramp(v: f32, low: f32, high: f32) -> f32 {
+  ((v - low) / (high - low)).clamp(0., 1.)
+}
+
+/// scores a window of tiles, from 0 to 1
+///
+/// graphics have correlated bitplanes and neighbouring pixels, a moderate byte entropy, and often
+/// repeat tiles or contain blank ones \
+/// blank tiles are left out of the statistics, so that fills do not look like graphics
+fn score_window(stats: &[TileStats], hashes: &[u64], entropy: f32, bits_per_pixel: usize) -> f32 {
+  let n = stats.len() as f32;
+  let filled: Vec<&TileStats> = stats.iter().filter(|s| !s.blank).collect();
+  if filled.len() * 4 < stats.len() {
+    return 0.;
+  }
+  let blank = 1. - filled.len() as f32 / n;
+  let mean =
+    |f: fn(&TileStats) -> f32| filled.iter().map(|s| f(s)).sum::<f32>() / filled.len() as f32;
+
+  // random pixels are equal to their neighbours once every 2^bpp pixels
+  let similarity = ramp(
+    mean(|s| s.similarity),
+    1. / (1 << bits_per_pixel) as f32,
+    0.75,
+  );
+  let plane = ramp(mean(|s| s.plane_agreement), 0.5, 0.8);
+  // random bytes are close to 7 bits per byte on windows of a few hundred bytes
+  let entropy = 1. - ramp(entropy, 4.5, 6.5);
+
+  let mut seen: HashMap<u64, usize> = HashMap::new();
+  for (s, h) in stats.iter().zip(hashes.iter()) {
+    if !s.blank {
+      *seen.entry(*h).or_default() += 1;
+    }
+  }
+  let repeated = seen.values().filter(|c| **c > 1).copied().sum::<usize>() as f32 / n;
+  let structure = (repeated + blank).min(1.);
+
+  0.4 * similarity + 0.25 * plane + 0.25 * entropy + 0.1 * structure
+}
+
+fn hash_tile(b: &[u8]) -> u64 {
+  let mut h = DefaultHasher::new();
+  b.hash(&mut h);
+  h.finish()
+}
+
+/// scans a binary for regions that look like chr data
+///
+/// windows of [`ScanOptions::window`] tiles are scored at every tile step, and consecutive windows
+/// above [`ScanOptions::threshold`] are merged into candidates, sorted by address
+pub fn scan(b: &[u8], options: &ScanOptions) -> Vec<ScanCandidate> {
+  let codec = options.format.codec();
+  let step = codec.bytes_per_tile();
+  let window = options.window.max(1);
+  let tiles = b.len() / step;
+  if tiles < window {
+    return Vec::new();
+  }
+
+  let bits_per_pixel = codec.bits_per_pixel();
+  let chunks: Vec<&[u8]> = b.chunks_exact(step).collect();
+  let stats: Vec<TileStats> = chunks
+    .iter()
+    .map(|c| TileStats::new(&codec.decode(c), bits_per_pixel))
+    .collect();
+  let hashes: Vec<u64> = chunks.iter().map(|c| hash_tile(c)).collect();
+
+  // byte histogram of the non-blank tiles of the window
+  let mut histogram = [0; 256];
+  let count = |histogram: &mut [usize; 256], i: usize, add: bool| {
+    if stats[i].blank {
+      return;
+    }
+    for v in chunks[i] {
+      match add {
+        true => histogram[*v as usize] += 1,
+        false => histogram[*v as usize] -= 1,
+      }
+    }
+  };
+  for i in 0..window {
+    count(&mut histogram, i, true);
+  }
+
+  // runs of windows above the threshold: first tile, end tile, sum of the scores, number of windows
+  let mut runs: Vec<(usize, usize, f32, usize)> = Vec::new();
+  let mut running = false;
+  for i in 0..=tiles - window {
+    if i > 0 {
+      count(&mut histogram, i - 1, false);
+      count(&mut histogram, i + window - 1, true);
+    }
+
+    let score = score_window(
+      &stats[i..i + window],
+      &hashes[i..i + window],
+      entropy(&histogram),
+      bits_per_pixel,
+    );
+
+    if score < options.threshold {
+      running = false;
+      continue;
+    }
+
+    // runs that overlap the previous one are merged into it
+    match runs.last_mut() {
+      Some((_, end, sum, count)) if running || i < *end => {
+        *end = i + window;
+        *sum += score;
+        *count += 1;
+      }
+      _ => runs.push((i, i + window, score, 1)),
+    }
+    running = true;
+  }
+
+  let mut candidates = Vec::new();
+  for (mut first, mut end, sum, count) in runs {
+    // blank tiles on the edges are fills around the graphics
+    while first < end && stats[first].blank {
+      first += 1;
+    }
+    while end > first && stats[end - 1].blank {
+      end -= 1;
+    }
+
+    candidates.push(ScanCandidate {
+      start: first * step,
+      length: (end - first) * step,
+      confidence: sum / count as f32,
+    });
+  }
+
+  candidates
+}
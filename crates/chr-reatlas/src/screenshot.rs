@@ -0,0 +1,176 @@
+use image::RgbaImage;
+
+use crate::{
+  atlas::{Atlas, AtlasData},
+  codec::TilePattern,
+  find::{TileIndex, image_cell, normalize_pattern},
+  pal::nearest_color,
+};
+
+/// options of the screenshot atlas builder
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScreenshotOptions {
+  /// offset of the 8x8 grid in the screenshot, searched if `None`
+  pub grid_offset: Option<(u32, u32)>,
+  /// also look for tiles off the grid, like sprites
+  ///
+  /// a sprite is only found where the pixels around it, under its colour 0, are of a single colour
+  pub sprites: bool,
+}
+
+/// an atlas built from a screenshot
+#[derive(Clone, Debug)]
+pub struct ScreenshotAtlas {
+  /// atlas reproducing the screenshot
+  pub atlas: Atlas,
+  /// offset of the 8x8 grid in the screenshot
+  pub grid_offset: (u32, u32),
+  /// positions of the grid cells that the atlas does not entirely reproduce
+  pub unmatched: Vec<(u32, u32)>,
+}
+
+/// matches the 8x8 window of an image at a pixel position with a tile, and picks its colours
+fn match_window(
+  image: &RgbaImage,
+  x: u32,
+  y: u32,
+  index: &TileIndex,
+  chrs: &[TilePattern],
+  pal: &[[u8; 3]],
+  bits_per_pixel: usize,
+) -> Option<AtlasData> {
+  let cell = image_cell(image, x, y);
+  let (chr_index, transform) = index.get(&normalize_pattern(&cell))?;
+  let mut tile = chrs[chr_index];
+  transform.apply(&mut tile);
+
+  // colour of every pixel value, the shape match makes it unique
+  let mut colors: Vec<Option<[u8; 4]>> = vec![None; 1 << bits_per_pixel];
+  for (tile_row, cell_row) in tile.iter().zip(cell.iter()) {
+    for (v, c) in tile_row.iter().zip(cell_row.iter()) {
+      colors[*v as usize] = Some(*c);
+    }
+  }
+
+  // transparent pixels can only be colour 0
+  let mut values = Vec::new();
+  for c in &colors[1..] {
+    match c {
+      Some(c) if c[3] < 128 => return None,
+      Some(c) => values.push(nearest_color(pal, [c[0], c[1], c[2]])),
+      None => values.push(0),
+    }
+  }
+  let cbg = colors[0]
+    .filter(|c| c[3] >= 128)
+    .map(|c| nearest_color(pal, [c[0], c[1], c[2]]));
+
+  Some(AtlasData {
+    chr_index,
+    c0: values.first().copied().unwrap_or(0),
+    c1: values.get(1).copied().unwrap_or(0),
+    c2: values.get(2).copied().unwrap_or(0),
+    colors: match values.len() > 3 {
+      true => values,
+      false => Vec::new(),
+    },
+    x,
+    y,
+    transpose: transform.transpose,
+    flip_x: transform.flip_x,
+    flip_y: transform.flip_y,
+    cbg,
+    ..Default::default()
+  })
+}
+
+/// whether every pixel of a cell has the same colour
+fn is_uniform(image: &RgbaImage, x: u32, y: u32) -> bool {
+  let cell = image_cell(image, x, y);
+  cell.iter().flatten().all(|c| *c == cell[0][0])
+}
+
+impl Atlas {
+  /// builds an atlas reproducing a screenshot with the chrs of the main source of this atlas
+  ///
+  /// the screenshot is cut into 8x8 cells, every cell is matched with a tile under any flip or
+  /// transposition, and its colours are taken from the closest colours of the palette \
+  /// the returned atlas is a copy of this one with its data replaced
+  pub fn build_from_screenshot(
+    &self,
+    image: &RgbaImage,
+    options: &ScreenshotOptions,
+  ) -> Result<ScreenshotAtlas, crate::Error> {
+    let chrs = self.get_chrs()?;
+    let pal = self.get_palette()?;
+    let bits_per_pixel = self.source.format.codec().bits_per_pixel();
+    let index = TileIndex::new(&chrs);
+    let window = |x, y| match_window(image, x, y, &index, &chrs, &pal, bits_per_pixel);
+
+    let cells = |(ox, oy): (u32, u32)| {
+      let columns = image.width().saturating_sub(ox) / 8;
+      let rows = image.height().saturating_sub(oy) / 8;
+      (0..rows).flat_map(move |r| (0..columns).map(move |c| (ox + c * 8, oy + r * 8)))
+    };
+
+    // the grid offset with the most matching cells, plain cells match anything and are not counted
+    let grid_offset = options.grid_offset.unwrap_or_else(|| {
+      (0..64)
+        .map(|i| (i % 8, i / 8))
+        .max_by_key(|offset| {
+          let matching = cells(*offset)
+            .filter(|(x, y)| !is_uniform(image, *x, *y) && window(*x, *y).is_some())
+            .count();
+          (
+            matching,
+            std::cmp::Reverse(offset.1),
+            std::cmp::Reverse(offset.0),
+          )
+        })
+        .unwrap_or((0, 0))
+    });
+
+    // pixels reproduced by the data
+    let width = image.width() as usize;
+    let mut covered = vec![false; width * image.height() as usize];
+    let pixels = |x: u32, y: u32| {
+      (y..y + 8).flat_map(move |py| (x..x + 8).map(move |px| py as usize * width + px as usize))
+    };
+
+    let mut data = Vec::new();
+    for (x, y) in cells(grid_offset) {
+      if let Some(d) = window(x, y) {
+        data.push(d);
+        pixels(x, y).for_each(|p| covered[p] = true);
+      }
+    }
+
+    if options.sprites && image.width() >= 8 && image.height() >= 8 {
+      for y in 0..=image.height() - 8 {
+        for x in 0..=image.width() - 8 {
+          if pixels(x, y).all(|p| covered[p]) || is_uniform(image, x, y) {
+            continue;
+          }
+
+          if let Some(d) = window(x, y) {
+            data.push(d);
+            pixels(x, y).for_each(|p| covered[p] = true);
+          }
+        }
+      }
+    }
+
+    let unmatched = cells(grid_offset)
+      .filter(|(x, y)| pixels(*x, *y).any(|p| !covered[p]))
+      .collect();
+
+    Ok(ScreenshotAtlas {
+      atlas: Atlas {
+        data,
+        ..self.clone()
+      },
+      grid_offset,
+      unmatched,
+    })
+  }
+}
@@ -0,0 +1,211 @@
+use std::fmt::Write;
+
+use crate::atlas::{Atlas, AtlasData};
+
+/// what a code of a text table stands for
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TextCode {
+  /// characters, one or more
+  Text(String),
+  /// a line break, written `*XX` in a .tbl
+  Newline,
+  /// the end of a string, written `/XX` in a .tbl
+  End,
+}
+
+/// a table mapping bytes to characters, like the .tbl files of ROM hacking tools
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct TextTable {
+  /// codes, as bytes, and what they stand for
+  pub entries: Vec<(Vec<u8>, TextCode)>,
+}
+
+/// parses an even number of hexadecimal digits as bytes
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+  if s.is_empty() || !s.len().is_multiple_of(2) || !s.is_ascii() {
+    return None;
+  }
+  (0..s.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+    .collect()
+}
+
+fn format_hex_bytes(b: &[u8]) -> String {
+  b.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+impl TextTable {
+  /// parses the text of a .tbl file
+  ///
+  /// lines are `XX=text`, `*XX` for line breaks and `/XX` for ends of strings, codes can be
+  /// several bytes long (like `8140= `) \
+  /// empty lines are ignored
+  pub fn from_tbl(s: &str) -> Result<Self, crate::Error> {
+    let mut entries = Vec::new();
+
+    for (i, line) in s.trim_start_matches('\u{FEFF}').lines().enumerate() {
+      let line = line.trim_end_matches('\r');
+      if line.trim().is_empty() {
+        continue;
+      }
+      let error = || crate::Error::TextTableParseError(i + 1, line.to_string());
+
+      let (code, entry) = if let Some(rest) = line.strip_prefix('*') {
+        (rest.split('=').next().unwrap(), TextCode::Newline)
+      } else if let Some(rest) = line.strip_prefix('/') {
+        (rest.split('=').next().unwrap(), TextCode::End)
+      } else {
+        let (code, text) = line.split_once('=').ok_or_else(error)?;
+        (code, TextCode::Text(text.to_string()))
+      };
+      entries.push((parse_hex_bytes(code.trim()).ok_or_else(error)?, entry));
+    }
+
+    Ok(Self { entries })
+  }
+
+  /// writes the table as the text of a .tbl file
+  pub fn to_tbl(&self) -> String {
+    let mut out = String::new();
+    for (code, entry) in &self.entries {
+      match entry {
+        TextCode::Text(text) => writeln!(out, "{}={}", format_hex_bytes(code), text),
+        TextCode::Newline => writeln!(out, "*{}", format_hex_bytes(code)),
+        TextCode::End => writeln!(out, "/{}", format_hex_bytes(code)),
+      }
+      .unwrap();
+    }
+    out
+  }
+
+  /// returns the longest code at the start of `b`, with its length
+  fn next_code(&self, b: &[u8]) -> Option<(usize, &TextCode)> {
+    self
+      .entries
+      .iter()
+      .filter(|(code, _)| b.starts_with(code))
+      .max_by_key(|(code, _)| code.len())
+      .map(|(code, entry)| (code.len(), entry))
+  }
+
+  /// decodes bytes to text, up to the first end of string
+  ///
+  /// bytes missing from the table are written `[XX]`
+  pub fn decode(&self, b: &[u8]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < b.len() {
+      match self.next_code(&b[i..]) {
+        Some((_, TextCode::End)) => break,
+        Some((len, entry)) => {
+          match entry {
+            TextCode::Text(text) => out.push_str(text),
+            _ => out.push('\n'),
+          }
+          i += len;
+        }
+        None => {
+          write!(out, "[{:02X}]", b[i]).unwrap();
+          i += 1;
+        }
+      }
+    }
+    out
+  }
+
+  /// encodes text to bytes, using the longest characters of the table first
+  ///
+  /// line breaks use the newline code of the table, and `[XX]` writes bytes as they are
+  pub fn encode(&self, s: &str) -> Result<Vec<u8>, crate::Error> {
+    let mut out = Vec::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+      // raw bytes
+      if let Some((hex, bytes)) = rest
+        .strip_prefix('[')
+        .and_then(|r| r.split_once(']'))
+        .and_then(|(hex, _)| parse_hex_bytes(hex).map(|b| (hex, b)))
+      {
+        out.extend(bytes);
+        rest = &rest[hex.len() + 2..];
+        continue;
+      }
+
+      let code = match c {
+        '\n' => self
+          .entries
+          .iter()
+          .find(|(_, entry)| *entry == TextCode::Newline)
+          .map(|(code, _)| (code, 1)),
+        _ => self
+          .entries
+          .iter()
+          .filter_map(|(code, entry)| match entry {
+            TextCode::Text(text) if !text.is_empty() && rest.starts_with(text.as_str()) => {
+              Some((code, text.len()))
+            }
+            _ => None,
+          })
+          .max_by_key(|(_, len)| *len),
+      };
+      let (code, len) = code.ok_or(crate::Error::TextEncodeError(c))?;
+      out.extend(code);
+      rest = &rest[len..];
+    }
+
+    Ok(out)
+  }
+}
+
+impl Atlas {
+  /// adds bytes of text as data, with the font tiles of a source
+  ///
+  /// every byte of a character draws tile `d.chr_index + byte` of the source of `d`, from `d.x`
+  /// and `d.y`, other fields are copied from `d` \
+  /// line breaks of the table start a new line, the text stops at the first end of string, and
+  /// lines wrap after `columns` tiles if given \
+  /// returns the number of data added
+  pub fn add_text(
+    &mut self,
+    table: &TextTable,
+    b: &[u8],
+    d: &AtlasData,
+    columns: Option<usize>,
+  ) -> usize {
+    let (mut column, mut line) = (0, 0);
+    let mut count = 0;
+    let mut i = 0;
+
+    while i < b.len() {
+      let len = match table.next_code(&b[i..]) {
+        Some((_, TextCode::End)) => break,
+        Some((len, TextCode::Newline)) => {
+          (column, line) = (0, line + 1);
+          i += len;
+          continue;
+        }
+        Some((len, TextCode::Text(_))) => len,
+        None => 1,
+      };
+
+      for byte in &b[i..i + len] {
+        if columns.is_some_and(|c| column >= c) {
+          (column, line) = (0, line + 1);
+        }
+        self.data.push(AtlasData {
+          chr_index: d.chr_index + *byte as usize,
+          x: d.x + column as u32 * 8,
+          y: d.y + line as u32 * 8,
+          ..d.clone()
+        });
+        column += 1;
+        count += 1;
+      }
+      i += len;
+    }
+
+    count
+  }
+}
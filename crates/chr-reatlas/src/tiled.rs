@@ -0,0 +1,1067 @@
+use image::RgbaImage;
+use roxmltree::{Document, Node};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+  atlas::{Atlas, AtlasBackground, AtlasData},
+  nametable::{NAMETABLE_HEIGHT, NAMETABLE_WIDTH},
+  render::{append_tile_on_image, greyscale_colors},
+};
+
+/// gid bit flipping a tile horizontally
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+/// gid bit flipping a tile vertically
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// gid bit flipping a tile over its top left to bottom right diagonal, applied before the others
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// bits of a gid holding the tile, bit 28 is used by Tiled for hexagonal rotations
+const GID_MASK: u32 = 0x0FFF_FFFF;
+/// tiles per line of a tileset sheet
+const TILESET_COLUMNS: usize = 16;
+/// Tiled format version written in maps and tilesets
+const TILED_VERSION: &str = "1.10";
+
+/// returns the gid of a tile with its flip flags
+pub fn encode_gid(tile: u32, flip_x: bool, flip_y: bool, transpose: bool) -> u32 {
+  let mut gid = tile & GID_MASK;
+  if flip_x {
+    gid |= FLIPPED_HORIZONTALLY;
+  }
+  if flip_y {
+    gid |= FLIPPED_VERTICALLY;
+  }
+  if transpose {
+    gid |= FLIPPED_DIAGONALLY;
+  }
+  gid
+}
+
+/// returns the tile of a gid and its flips (x, y and transpose)
+pub fn decode_gid(gid: u32) -> (u32, bool, bool, bool) {
+  (
+    gid & GID_MASK,
+    gid & FLIPPED_HORIZONTALLY != 0,
+    gid & FLIPPED_VERTICALLY != 0,
+    gid & FLIPPED_DIAGONALLY != 0,
+  )
+}
+
+/// a `.tsx` tileset, the chrs of an atlas source drawn with one set of colours
+///
+/// the source and colours are kept in the properties of the tileset, so that tiles of a map come
+/// back as atlas data
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TiledTileset {
+  /// name of the tileset
+  pub name: String,
+  /// path of the sheet, relative to the tileset
+  pub image: String,
+  /// width of the sheet, in pixels
+  pub image_width: u32,
+  /// height of the sheet, in pixels
+  pub image_height: u32,
+  /// number of tiles
+  pub tile_count: usize,
+  /// number of tiles per line of the sheet
+  pub columns: usize,
+  /// chr source of the tiles, the main source if `None`
+  pub source: Option<String>,
+  /// color 0
+  pub c0: usize,
+  /// color 1
+  pub c1: usize,
+  /// color 2
+  pub c2: usize,
+  /// colors of pixel values 1 and up, see [`AtlasData::colors`]
+  pub colors: Vec<usize>,
+  /// palette line, see [`AtlasData::line`]
+  pub line: Option<usize>,
+  /// color background
+  pub cbg: Option<usize>,
+}
+
+impl TiledTileset {
+  /// returns a data drawing a tile of the tileset
+  fn data(&self, chr_index: usize, x: u32, y: u32, gid: u32) -> AtlasData {
+    let (_, flip_x, flip_y, transpose) = decode_gid(gid);
+    AtlasData {
+      source: self.source.clone(),
+      chr_index,
+      c0: self.c0,
+      c1: self.c1,
+      c2: self.c2,
+      colors: self.colors.clone(),
+      line: self.line,
+      x,
+      y,
+      transpose,
+      flip_x,
+      flip_y,
+      cbg: self.cbg,
+      ..Default::default()
+    }
+  }
+
+  /// returns the text of the tileset
+  pub fn to_tsx(&self) -> String {
+    format!(
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+      self.to_xml(None)
+    )
+  }
+
+  /// returns the `<tileset>` element of the tileset, embedded in a map if given a first gid
+  fn to_xml(&self, firstgid: Option<u32>) -> String {
+    let mut out = String::new();
+    let firstgid = firstgid.map_or(String::new(), |g| format!(r#" firstgid="{}""#, g));
+    writeln!(
+      out,
+      r#"<tileset version="{}"{} name="{}" tilewidth="8" tileheight="8" tilecount="{}" columns="{}">"#,
+      TILED_VERSION,
+      firstgid,
+      escape(&self.name),
+      self.tile_count,
+      self.columns
+    )
+    .unwrap();
+
+    let mut properties: Vec<(&str, &str, String)> = Vec::new();
+    if let Some(source) = &self.source {
+      properties.push(("source", "string", source.clone()));
+    }
+    properties.push(("c0", "int", self.c0.to_string()));
+    properties.push(("c1", "int", self.c1.to_string()));
+    properties.push(("c2", "int", self.c2.to_string()));
+    if !self.colors.is_empty() {
+      let colors: Vec<String> = self.colors.iter().map(|c| c.to_string()).collect();
+      properties.push(("colors", "string", colors.join(",")));
+    }
+    if let Some(line) = self.line {
+      properties.push(("line", "int", line.to_string()));
+    }
+    if let Some(cbg) = self.cbg {
+      properties.push(("cbg", "int", cbg.to_string()));
+    }
+    writeln!(out, " <properties>").unwrap();
+    for (name, kind, value) in properties {
+      writeln!(
+        out,
+        r#"  <property name="{}" type="{}" value="{}"/>"#,
+        name,
+        kind,
+        escape(&value)
+      )
+      .unwrap();
+    }
+    writeln!(out, " </properties>").unwrap();
+
+    writeln!(
+      out,
+      r#" <image source="{}" width="{}" height="{}"/>"#,
+      escape(&self.image),
+      self.image_width,
+      self.image_height
+    )
+    .unwrap();
+    writeln!(out, "</tileset>").unwrap();
+    out
+  }
+
+  /// reads a tileset from its text
+  pub fn from_tsx(s: &str) -> Result<Self, crate::Error> {
+    let document = parse_xml(s)?;
+    let tileset = document.root_element();
+    if !tileset.has_tag_name("tileset") {
+      return Err(crate::Error::TiledParseError("no <tileset>".to_string()));
+    }
+    Self::from_node(tileset)
+  }
+
+  /// reads a tileset from its `<tileset>` element
+  fn from_node(tileset: Node) -> Result<Self, crate::Error> {
+    let image = child(tileset, "image")?;
+    let properties: BTreeMap<&str, &str> = tileset
+      .children()
+      .filter(|e| e.has_tag_name("properties"))
+      .flat_map(|e| e.children())
+      .filter(|e| e.has_tag_name("property"))
+      .map(|e| Ok((attribute(e, "name")?, attribute(e, "value")?)))
+      .collect::<Result<_, crate::Error>>()?;
+    let int = |name: &str| -> Result<Option<usize>, crate::Error> {
+      properties.get(name).map(|v| parse(name, v)).transpose()
+    };
+
+    Ok(Self {
+      name: tileset.attribute("name").unwrap_or_default().to_string(),
+      image: attribute(image, "source")?.to_string(),
+      image_width: parse("width", attribute(image, "width")?)?,
+      image_height: parse("height", attribute(image, "height")?)?,
+      tile_count: parse("tilecount", attribute(tileset, "tilecount")?)?,
+      columns: parse("columns", attribute(tileset, "columns")?)?,
+      source: properties.get("source").map(|s| s.to_string()),
+      c0: int("c0")?.unwrap_or(0),
+      c1: int("c1")?.unwrap_or(0),
+      c2: int("c2")?.unwrap_or(0),
+      colors: match properties.get("colors") {
+        Some(colors) => colors
+          .split(',')
+          .map(|c| parse("colors", c))
+          .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+      },
+      line: int("line")?,
+      cbg: int("cbg")?,
+    })
+  }
+}
+
+/// a tileset used by a map, from its first gid
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TiledTilesetRef {
+  /// gid of the first tile of the tileset
+  pub firstgid: u32,
+  /// path of the `.tsx`, relative to the map, empty for an embedded tileset
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub source: String,
+  /// tileset embedded in the map, only read from and written to a `.tmx`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub tileset: Option<TiledTileset>,
+}
+
+/// a grid of tiles of a map
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiledTileLayer {
+  /// id of the layer
+  pub id: u32,
+  /// name of the layer
+  pub name: String,
+  /// width, in tiles
+  pub width: u32,
+  /// height, in tiles
+  pub height: u32,
+  /// x offset, in tiles
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub x: i32,
+  /// y offset, in tiles
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub y: i32,
+  /// opacity
+  #[cfg_attr(feature = "serde", serde(default = "default_opacity"))]
+  pub opacity: f64,
+  /// whether the layer is shown
+  #[cfg_attr(feature = "serde", serde(default = "default_visible"))]
+  pub visible: bool,
+  /// gids of the cells, row by row, 0 for an empty cell
+  pub data: Vec<u32>,
+}
+
+/// a tile placed anywhere on a map
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiledObject {
+  /// id of the object
+  pub id: u32,
+  /// name of the object
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub name: String,
+  /// tile and flips of the object, 0 for an object that is not a tile
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub gid: u32,
+  /// x of the left of the tile
+  pub x: f64,
+  /// y of the bottom of the tile
+  pub y: f64,
+  /// width, in pixels
+  pub width: f64,
+  /// height, in pixels
+  pub height: f64,
+  /// rotation, in degrees
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub rotation: f64,
+  /// whether the object is shown
+  #[cfg_attr(feature = "serde", serde(default = "default_visible"))]
+  pub visible: bool,
+}
+
+/// a layer of tiles placed anywhere on a map
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiledObjectLayer {
+  /// id of the layer
+  pub id: u32,
+  /// name of the layer
+  pub name: String,
+  /// x offset, in tiles
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub x: i32,
+  /// y offset, in tiles
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub y: i32,
+  /// opacity
+  #[cfg_attr(feature = "serde", serde(default = "default_opacity"))]
+  pub opacity: f64,
+  /// whether the layer is shown
+  #[cfg_attr(feature = "serde", serde(default = "default_visible"))]
+  pub visible: bool,
+  /// drawing order of the objects
+  #[cfg_attr(feature = "serde", serde(default = "default_draworder"))]
+  pub draworder: String,
+  /// objects of the layer
+  pub objects: Vec<TiledObject>,
+}
+
+/// a layer of a map
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+#[derive(Clone, PartialEq, Debug)]
+pub enum TiledLayer {
+  /// grid of tiles
+  #[cfg_attr(feature = "serde", serde(rename = "tilelayer"))]
+  Tiles(TiledTileLayer),
+  /// tiles placed anywhere
+  #[cfg_attr(feature = "serde", serde(rename = "objectgroup"))]
+  Objects(TiledObjectLayer),
+  /// any other layer, left out
+  #[cfg_attr(feature = "serde", serde(other))]
+  Other,
+}
+
+/// an orthogonal map of 8x8 tiles, written as a `.tmx` or in Tiled's JSON format
+///
+/// field names follow Tiled's JSON format
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiledMap {
+  /// always `map`
+  #[cfg_attr(feature = "serde", serde(rename = "type"))]
+  pub kind: String,
+  /// version of the format
+  pub version: String,
+  /// always `orthogonal`
+  pub orientation: String,
+  /// order the tiles are drawn in
+  pub renderorder: String,
+  /// always `false`
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub infinite: bool,
+  /// width, in tiles
+  pub width: u32,
+  /// height, in tiles
+  pub height: u32,
+  /// width of a tile, in pixels
+  pub tilewidth: u32,
+  /// height of a tile, in pixels
+  pub tileheight: u32,
+  /// id of the next layer to be added
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub nextlayerid: u32,
+  /// id of the next object to be added
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub nextobjectid: u32,
+  /// tilesets, from their first gid
+  pub tilesets: Vec<TiledTilesetRef>,
+  /// layers, from bottom to top
+  pub layers: Vec<TiledLayer>,
+}
+
+#[cfg(feature = "serde")]
+fn default_opacity() -> f64 {
+  1.0
+}
+
+#[cfg(feature = "serde")]
+fn default_visible() -> bool {
+  true
+}
+
+#[cfg(feature = "serde")]
+fn default_draworder() -> String {
+  "index".to_string()
+}
+
+impl Default for TiledMap {
+  fn default() -> Self {
+    Self {
+      kind: "map".to_string(),
+      version: TILED_VERSION.to_string(),
+      orientation: "orthogonal".to_string(),
+      renderorder: "right-down".to_string(),
+      infinite: false,
+      width: 0,
+      height: 0,
+      tilewidth: 8,
+      tileheight: 8,
+      nextlayerid: 1,
+      nextobjectid: 1,
+      tilesets: Vec::new(),
+      layers: Vec::new(),
+    }
+  }
+}
+
+impl TiledMap {
+  /// returns the text of the map, layers are written as csv
+  pub fn to_tmx(&self) -> String {
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+      out,
+      r#"<map version="{}" orientation="{}" renderorder="{}" width="{}" height="{}" tilewidth="{}" tileheight="{}" infinite="{}" nextlayerid="{}" nextobjectid="{}">"#,
+      escape(&self.version),
+      escape(&self.orientation),
+      escape(&self.renderorder),
+      self.width,
+      self.height,
+      self.tilewidth,
+      self.tileheight,
+      self.infinite as u8,
+      self.nextlayerid,
+      self.nextobjectid
+    )
+    .unwrap();
+    for t in &self.tilesets {
+      match &t.tileset {
+        Some(tileset) => {
+          for line in tileset.to_xml(Some(t.firstgid)).lines() {
+            writeln!(out, " {}", line).unwrap();
+          }
+        }
+        None => writeln!(
+          out,
+          r#" <tileset firstgid="{}" source="{}"/>"#,
+          t.firstgid,
+          escape(&t.source)
+        )
+        .unwrap(),
+      }
+    }
+
+    for layer in &self.layers {
+      match layer {
+        TiledLayer::Tiles(l) => {
+          writeln!(
+            out,
+            r#" <layer id="{}" name="{}" width="{}" height="{}"{}>"#,
+            l.id,
+            escape(&l.name),
+            l.width,
+            l.height,
+            match l.visible {
+              true => "",
+              false => r#" visible="0""#,
+            }
+          )
+          .unwrap();
+          writeln!(out, r#"  <data encoding="csv">"#).unwrap();
+          let rows: Vec<String> = l
+            .data
+            .chunks(l.width.max(1) as usize)
+            .map(|row| {
+              row
+                .iter()
+                .map(|gid| gid.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+            })
+            .collect();
+          writeln!(out, "{}", rows.join(",\n")).unwrap();
+          writeln!(out, "</data>").unwrap();
+          writeln!(out, " </layer>").unwrap();
+        }
+        TiledLayer::Objects(l) => {
+          writeln!(
+            out,
+            r#" <objectgroup id="{}" name="{}">"#,
+            l.id,
+            escape(&l.name)
+          )
+          .unwrap();
+          for o in &l.objects {
+            let gid = match o.gid {
+              0 => String::new(),
+              gid => format!(r#" gid="{}""#, gid),
+            };
+            writeln!(
+              out,
+              r#"  <object id="{}"{} x="{}" y="{}" width="{}" height="{}"/>"#,
+              o.id, gid, o.x, o.y, o.width, o.height
+            )
+            .unwrap();
+          }
+          writeln!(out, " </objectgroup>").unwrap();
+        }
+        TiledLayer::Other => {}
+      }
+    }
+
+    writeln!(out, "</map>").unwrap();
+    out
+  }
+
+  /// reads a map from its text, layers must be written as csv
+  ///
+  /// tilesets can be external or embedded in the map
+  pub fn from_tmx(s: &str) -> Result<Self, crate::Error> {
+    let document = parse_xml(s)?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+      return Err(crate::Error::TiledParseError("no <map>".to_string()));
+    }
+    let visible = |e: Node| e.attribute("visible") != Some("0");
+    let name = |e: Node| e.attribute("name").unwrap_or_default().to_string();
+    let mut tilesets = Vec::new();
+    let mut layers = Vec::new();
+
+    for e in map.children().filter(|e| e.is_element()) {
+      match e.tag_name().name() {
+        "tileset" => tilesets.push(TiledTilesetRef {
+          firstgid: parse("firstgid", attribute(e, "firstgid")?)?,
+          source: e.attribute("source").unwrap_or_default().to_string(),
+          tileset: match e.attribute("source") {
+            Some(_) => None,
+            None => Some(TiledTileset::from_node(e)?),
+          },
+        }),
+        "layer" => {
+          let data = child(e, "data")?;
+          let encoding = data.attribute("encoding").unwrap_or_default();
+          if encoding != "csv" {
+            return Err(crate::Error::TiledParseError(format!(
+              "layer encoding \"{}\" is not supported, use csv",
+              encoding
+            )));
+          }
+          layers.push(TiledLayer::Tiles(TiledTileLayer {
+            id: parse("id", attribute(e, "id")?)?,
+            name: name(e),
+            width: parse("width", attribute(e, "width")?)?,
+            height: parse("height", attribute(e, "height")?)?,
+            x: 0,
+            y: 0,
+            opacity: 1.0,
+            visible: visible(e),
+            data: data
+              .text()
+              .unwrap_or_default()
+              .split(|c: char| c == ',' || c.is_whitespace())
+              .filter(|v| !v.is_empty())
+              .map(|v| parse("data", v))
+              .collect::<Result<_, _>>()?,
+          }));
+        }
+        "objectgroup" => {
+          let objects = e
+            .children()
+            .filter(|o| o.has_tag_name("object"))
+            .map(|o| {
+              Ok(TiledObject {
+                id: parse("id", attribute(o, "id")?)?,
+                name: name(o),
+                gid: parse("gid", o.attribute("gid").unwrap_or("0"))?,
+                x: parse("x", attribute(o, "x")?)?,
+                y: parse("y", attribute(o, "y")?)?,
+                width: parse("width", o.attribute("width").unwrap_or("8"))?,
+                height: parse("height", o.attribute("height").unwrap_or("8"))?,
+                rotation: 0.0,
+                visible: visible(o),
+              })
+            })
+            .collect::<Result<_, crate::Error>>()?;
+          layers.push(TiledLayer::Objects(TiledObjectLayer {
+            id: parse("id", attribute(e, "id")?)?,
+            name: name(e),
+            x: 0,
+            y: 0,
+            opacity: 1.0,
+            visible: visible(e),
+            draworder: "index".to_string(),
+            objects,
+          }));
+        }
+        _ => {}
+      }
+    }
+
+    Ok(Self {
+      version: map.attribute("version").unwrap_or_default().to_string(),
+      orientation: attribute(map, "orientation")?.to_string(),
+      renderorder: map
+        .attribute("renderorder")
+        .unwrap_or("right-down")
+        .to_string(),
+      infinite: map.attribute("infinite") == Some("1"),
+      width: parse("width", attribute(map, "width")?)?,
+      height: parse("height", attribute(map, "height")?)?,
+      tilewidth: parse("tilewidth", attribute(map, "tilewidth")?)?,
+      tileheight: parse("tileheight", attribute(map, "tileheight")?)?,
+      nextlayerid: parse("nextlayerid", map.attribute("nextlayerid").unwrap_or("0"))?,
+      nextobjectid: parse("nextobjectid", map.attribute("nextobjectid").unwrap_or("0"))?,
+      tilesets,
+      layers,
+      ..Default::default()
+    })
+  }
+
+  /// returns the tilesets of the map, in order
+  ///
+  /// embedded tilesets are used as they are, and external ones are read with `read`, given their
+  /// path relative to the map \
+  /// only `.tsx` tilesets can be read, JSON tilesets and tilesets embedded in JSON maps are not
+  /// supported
+  pub fn get_tilesets(
+    &self,
+    read: impl Fn(&str) -> Result<String, crate::Error>,
+  ) -> Result<Vec<TiledTileset>, crate::Error> {
+    self
+      .tilesets
+      .iter()
+      .map(|r| match &r.tileset {
+        Some(tileset) => Ok(tileset.clone()),
+        None if r.source.is_empty() => Err(crate::Error::TiledParseError(format!(
+          "tileset at gid {} is embedded in a JSON map, save it as a .tsx",
+          r.firstgid
+        ))),
+        None if !r.source.to_lowercase().ends_with(".tsx") => Err(crate::Error::TiledParseError(
+          format!("tileset \"{}\" is not a .tsx", r.source),
+        )),
+        None => TiledTileset::from_tsx(&read(&r.source)?),
+      })
+      .collect()
+  }
+}
+
+/// a map of an atlas, with its tilesets and their sheets
+#[derive(Clone, Debug)]
+pub struct TiledExport {
+  /// map, referencing the tilesets by their index in `tilesets`
+  pub map: TiledMap,
+  /// tilesets, in the order of the map
+  pub tilesets: Vec<TiledTileset>,
+  /// sheets of the tilesets
+  pub images: Vec<RgbaImage>,
+}
+
+impl Atlas {
+  /// draws every chr of the source of a data with its colours, as a tileset
+  ///
+  /// tiles needing more colours than the data has use greys for the missing ones
+  pub fn get_tiled_tileset(
+    &self,
+    name: &str,
+    image: &str,
+    d: &AtlasData,
+  ) -> Result<(TiledTileset, RgbaImage), crate::Error> {
+    let chrs = self.get_all_chrs()?;
+    let tiles = chrs.source(d.source.as_deref())?;
+    let pal = self.get_palette()?;
+    let bits_per_pixel = self
+      .get_source(d.source.as_deref())?
+      .format
+      .codec()
+      .bits_per_pixel();
+
+    let colors = self.get_colors(d)?;
+    if let Some(c) = colors.iter().chain(d.cbg.iter()).find(|c| **c >= pal.len()) {
+      return Err(crate::Error::AtlasPaletteIndexError(*c, pal.len()));
+    }
+    let mut colors: Vec<[u8; 3]> = colors.iter().map(|c| pal[*c]).collect();
+    let greys = greyscale_colors(bits_per_pixel);
+    if colors.len() < greys.len() {
+      colors.extend_from_slice(&greys[colors.len()..]);
+    }
+
+    let rows = tiles.len().div_ceil(TILESET_COLUMNS).max(1);
+    let mut img = RgbaImage::new(TILESET_COLUMNS as u32 * 8, rows as u32 * 8);
+    for (i, tile) in tiles.iter().enumerate() {
+      let (x, y) = (i % TILESET_COLUMNS, i / TILESET_COLUMNS);
+      append_tile_on_image(
+        &mut img,
+        tile,
+        x as u32 * 8,
+        y as u32 * 8,
+        &colors,
+        d.cbg.map(|c| pal[c]),
+      );
+    }
+
+    let tileset = TiledTileset {
+      name: name.to_string(),
+      image: image.to_string(),
+      image_width: img.width(),
+      image_height: img.height(),
+      tile_count: tiles.len(),
+      columns: TILESET_COLUMNS,
+      source: d.source.clone(),
+      c0: d.c0,
+      c1: d.c1,
+      c2: d.c2,
+      colors: d.colors.clone(),
+      line: d.line,
+      cbg: d.cbg,
+    };
+    Ok((tileset, img))
+  }
+
+  /// converts the atlas to a map, with a tileset for every source and colours used
+  ///
+  /// a nametable background becomes a `background` layer \
+  /// data aligned on the 8x8 grid go in `data` layers, other data become tile objects of `objects`
+  /// layers, layers being stacked to keep the drawing order of the data \
+  /// sprite priority and groups are not kept \
+  /// tilesets and sheets are named `<name>_<index>.tsx` and `<name>_<index>.png`
+  pub fn to_tiled(&self, name: &str) -> Result<TiledExport, crate::Error> {
+    let mut templates: Vec<AtlasData> = Vec::new();
+    let mut background: Vec<(usize, usize, usize)> = Vec::new();
+    let (mut width, mut height) = self.data.iter().fold((0, 0), |acc, d| {
+      (
+        acc.0.max((d.x + 8).div_ceil(8)),
+        acc.1.max((d.y + 8).div_ceil(8)),
+      )
+    });
+
+    // every tile of the background, as a tileset and a tile
+    if let Some(AtlasBackground::Nametable(n)) = &self.background {
+      let nt = n.get_nametable()?;
+      width = width.max(NAMETABLE_WIDTH as u32);
+      height = height.max(NAMETABLE_HEIGHT as u32);
+      for ty in 0..NAMETABLE_HEIGHT {
+        for tx in 0..NAMETABLE_WIDTH {
+          let p = n.palettes[nt.sub_palette(tx, ty)];
+          let template = AtlasData {
+            c0: p[0],
+            c1: p[1],
+            c2: p[2],
+            cbg: Some(n.backdrop),
+            ..Default::default()
+          };
+          let t = template_index(&mut templates, &template);
+          background.push((
+            tx + ty * NAMETABLE_WIDTH,
+            t,
+            n.chr_offset + nt.tile(tx, ty) as usize,
+          ));
+        }
+      }
+    }
+    let data_templates: Vec<usize> = self
+      .data
+      .iter()
+      .map(|d| template_index(&mut templates, d))
+      .collect();
+
+    let mut tilesets = Vec::new();
+    let mut images = Vec::new();
+    let mut map = TiledMap {
+      width,
+      height,
+      ..Default::default()
+    };
+    let mut firstgid = 1;
+    for (i, template) in templates.iter().enumerate() {
+      let (tileset, img) = self.get_tiled_tileset(
+        &format!("{}_{}", name, i),
+        &format!("{}_{}.png", name, i),
+        template,
+      )?;
+      map.tilesets.push(TiledTilesetRef {
+        firstgid,
+        source: format!("{}_{}.tsx", name, i),
+        tileset: None,
+      });
+      firstgid += tileset.tile_count.max(1) as u32;
+      tilesets.push(tileset);
+      images.push(img);
+    }
+    let gid = |t: usize, chr_index: usize| map.tilesets[t].firstgid + chr_index as u32;
+
+    let cells = (width * height) as usize;
+    let mut layers: Vec<TiledLayer> = Vec::new();
+    let tile_layer = |name: &str, data: Vec<u32>| {
+      TiledLayer::Tiles(TiledTileLayer {
+        id: 0,
+        name: name.to_string(),
+        width,
+        height,
+        x: 0,
+        y: 0,
+        opacity: 1.0,
+        visible: true,
+        data,
+      })
+    };
+    if !background.is_empty() {
+      let mut data = vec![0; cells];
+      for (cell, t, chr_index) in &background {
+        let (tx, ty) = (cell % NAMETABLE_WIDTH, cell / NAMETABLE_WIDTH);
+        data[tx + ty * width as usize] = gid(*t, *chr_index);
+      }
+      layers.push(tile_layer("background", data));
+    }
+
+    // data from back to front, with a background the first data is in front like in OAM
+    let mut order: Vec<(&AtlasData, usize)> = self.data.iter().zip(data_templates).collect();
+    if self.background.is_some() {
+      order.reverse();
+    }
+    let first_data_layer = layers.len();
+    for (d, t) in order {
+      let g = encode_gid(gid(t, d.chr_index), d.flip_x, d.flip_y, d.transpose);
+      let aligned = d.x % 8 == 0 && d.y % 8 == 0;
+      let cell = (d.x / 8 + d.y / 8 * width) as usize;
+
+      // data go on the top layer, or on a new one if it cannot hold them
+      let has_top = layers.len() > first_data_layer;
+      let top = layers.last_mut().filter(|_| has_top);
+      match (top, aligned) {
+        (Some(TiledLayer::Tiles(l)), true) if l.data[cell] == 0 => l.data[cell] = g,
+        (Some(TiledLayer::Objects(l)), false) => l.objects.push(tile_object(d, g)),
+        (_, true) => {
+          let mut data = vec![0; cells];
+          data[cell] = g;
+          layers.push(tile_layer("data", data));
+        }
+        (_, false) => layers.push(TiledLayer::Objects(TiledObjectLayer {
+          id: 0,
+          name: "objects".to_string(),
+          x: 0,
+          y: 0,
+          opacity: 1.0,
+          visible: true,
+          draworder: "index".to_string(),
+          objects: vec![tile_object(d, g)],
+        })),
+      }
+    }
+
+    for layer in &mut layers {
+      let (id, name) = match layer {
+        TiledLayer::Tiles(l) => (&mut l.id, &mut l.name),
+        TiledLayer::Objects(l) => {
+          for o in &mut l.objects {
+            o.id = map.nextobjectid;
+            map.nextobjectid += 1;
+          }
+          (&mut l.id, &mut l.name)
+        }
+        TiledLayer::Other => continue,
+      };
+      *id = map.nextlayerid;
+      if map.nextlayerid as usize > first_data_layer + 1 {
+        *name = format!("{} {}", name, map.nextlayerid as usize - first_data_layer);
+      }
+      map.nextlayerid += 1;
+    }
+    map.layers = layers;
+
+    Ok(TiledExport {
+      map,
+      tilesets,
+      images,
+    })
+  }
+
+  /// adds the tiles of a map as data, layer by layer, returns the number of data added
+  ///
+  /// `tilesets` are the tilesets of the map, in order \
+  /// tiles drawn in front come last, or first in an atlas with a background \
+  /// Tiled's diagonal flip becomes [`AtlasData::transpose`], hidden layers and objects are left out
+  pub fn import_tiled(
+    &mut self,
+    map: &TiledMap,
+    tilesets: &[TiledTileset],
+  ) -> Result<usize, crate::Error> {
+    if map.tilewidth != 8 || map.tileheight != 8 {
+      return Err(crate::Error::TiledParseError(format!(
+        "tiles of {}x{} pixels, expected 8x8",
+        map.tilewidth, map.tileheight
+      )));
+    }
+
+    let tile = |gid: u32, x: i64, y: i64| -> Result<AtlasData, crate::Error> {
+      let (id, ..) = decode_gid(gid);
+      let (r, tileset) = map
+        .tilesets
+        .iter()
+        .zip(tilesets)
+        .filter(|(r, _)| r.firstgid <= id)
+        .max_by_key(|(r, _)| r.firstgid)
+        .ok_or(crate::Error::TiledGidError(id))?;
+      let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+        return Err(crate::Error::TiledParseError(format!(
+          "tile {:#X} at ({}, {}) is out of the atlas",
+          id, x, y
+        )));
+      };
+      Ok(tileset.data((id - r.firstgid) as usize, x, y, gid))
+    };
+
+    let mut layers = Vec::new();
+    for layer in &map.layers {
+      let mut data = Vec::new();
+      match layer {
+        TiledLayer::Tiles(l) if l.visible => {
+          for (i, gid) in l.data.iter().enumerate().filter(|(_, gid)| **gid != 0) {
+            let (x, y) = (i as u32 % l.width.max(1), i as u32 / l.width.max(1));
+            data.push(tile(*gid, x as i64 * 8, y as i64 * 8)?);
+          }
+        }
+        TiledLayer::Objects(l) if l.visible => {
+          for o in l.objects.iter().filter(|o| o.visible && o.gid != 0) {
+            // tile objects are placed by their bottom left corner
+            data.push(tile(o.gid, o.x.round() as i64, o.y.round() as i64 - 8)?);
+          }
+        }
+        _ => {}
+      }
+      layers.push(data);
+    }
+
+    // with a background, the first data is drawn in front like in OAM
+    let mut data: Vec<AtlasData> = layers.into_iter().flatten().collect();
+    if self.background.is_some() {
+      data.reverse();
+    }
+    let count = data.len();
+    self.data.extend(data);
+    Ok(count)
+  }
+}
+
+/// returns a tile object drawing a data
+fn tile_object(d: &AtlasData, gid: u32) -> TiledObject {
+  // tile objects are placed by their bottom left corner
+  TiledObject {
+    id: 0,
+    name: String::new(),
+    gid,
+    x: d.x as f64,
+    y: d.y as f64 + 8.0,
+    width: 8.0,
+    height: 8.0,
+    rotation: 0.0,
+    visible: true,
+  }
+}
+
+/// returns the index of the tileset of a data, adding it if it is new
+fn template_index(templates: &mut Vec<AtlasData>, d: &AtlasData) -> usize {
+  match templates.iter().position(|t| same_colors(t, d)) {
+    Some(i) => i,
+    None => {
+      templates.push(d.clone());
+      templates.len() - 1
+    }
+  }
+}
+
+/// whether two data have the same source and colours, and so the same tileset
+fn same_colors(a: &AtlasData, b: &AtlasData) -> bool {
+  a.source == b.source
+    && a.c0 == b.c0
+    && a.c1 == b.c1
+    && a.c2 == b.c2
+    && a.colors == b.colors
+    && a.line == b.line
+    && a.cbg == b.cbg
+}
+
+/// parses a number of a tmx or tsx
+fn parse<T: std::str::FromStr>(name: &str, v: &str) -> Result<T, crate::Error> {
+  v.trim()
+    .parse()
+    .map_err(|_| crate::Error::TiledParseError(format!("invalid {} \"{}\"", name, v)))
+}
+
+/// escapes text for an xml attribute
+fn escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// parses the text of a tmx or tsx
+fn parse_xml(s: &str) -> Result<Document<'_>, crate::Error> {
+  Document::parse(s).map_err(|e| crate::Error::TiledParseError(e.to_string()))
+}
+
+/// returns an attribute of an element
+fn attribute<'a>(e: Node<'a, '_>, name: &str) -> Result<&'a str, crate::Error> {
+  e.attribute(name).ok_or_else(|| {
+    crate::Error::TiledParseError(format!("<{}> has no {}", e.tag_name().name(), name))
+  })
+}
+
+/// returns the first child element of an element with a tag
+fn child<'a, 'i>(e: Node<'a, 'i>, tag: &str) -> Result<Node<'a, 'i>, crate::Error> {
+  e.children()
+    .find(|c| c.has_tag_name(tag))
+    .ok_or_else(|| crate::Error::TiledParseError(format!("no <{}>", tag)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    atlas::AtlasSource,
+    codec::TileFormat,
+    pal::PaletteFormat,
+    ppu::{PpuMask, PpuType},
+  };
+
+  const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="4" tilewidth="8" tileheight="8" infinite="0">
+ <tileset firstgid="1" name="chr" tilewidth="8" tileheight="8" tilecount="256" columns="16">
+  <image source="chr.png" width="128" height="128"/>
+ </tileset>
+ <objectgroup id="1" name="objects">
+  <object id="1" gid="2" x="8" y="16" width="8" height="8"/>
+  <object id="2" name="area" x="0" y="0" width="16" height="16"/>
+  <object id="3" name="spawn" x="4" y="4">
+   <point/>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+  #[test]
+  fn objects_without_gid() {
+    let map = TiledMap::from_tmx(TMX).unwrap();
+    let TiledLayer::Objects(l) = &map.layers[0] else {
+      panic!("expected an object layer");
+    };
+    let gids: Vec<u32> = l.objects.iter().map(|o| o.gid).collect();
+    assert_eq!(gids, [2, 0, 0]);
+
+    let mut atlas = Atlas {
+      source: AtlasSource {
+        binary: String::new(),
+        start: 0,
+        length: 0,
+        banks: Vec::new(),
+        format: TileFormat::Nes,
+        fds: None,
+        compression: None,
+      },
+      sources: BTreeMap::new(),
+      palette: String::new(),
+      palette_format: PaletteFormat::default(),
+      ppu: PpuType::default(),
+      mask: PpuMask::default(),
+      data: Vec::new(),
+      background: None,
+      groups: BTreeMap::new(),
+      animations: BTreeMap::new(),
+    };
+    let tilesets = map.get_tilesets(|_| unreachable!()).unwrap();
+    assert_eq!(atlas.import_tiled(&map, &tilesets).unwrap(), 1);
+    assert_eq!(
+      (atlas.data[0].chr_index, atlas.data[0].x, atlas.data[0].y),
+      (1, 8, 8)
+    );
+  }
+}
@@ -1,13 +1,24 @@
-use std::{
-  fs::{File, read_to_string},
-  io::{Read, Seek, SeekFrom},
-};
+use std::fs::read_to_string;
 
 use chr_reatlas::{
-  atlas::Atlas,
-  chr::read_bytes,
-  render::{render_patterns_with_graduations},
-  pal::ChrPalette
+  atlas::{Atlas, AtlasBackground, AtlasData, AtlasSource},
+  binary::{read_binary, read_region},
+  codec::{TileFormat, read_tiles},
+  compression::Compression,
+  diff::diff_chr,
+  fds::FdsImage,
+  find::{FindOptions, find_tiles},
+  fingerprint::{FingerprintIndex, FingerprintOptions, TileGroup},
+  import::{ImportOptions, import_image},
+  metasprite::{MetaspriteFormat, MetaspriteLayout},
+  nesst::{MetaspriteBank, NesstPalette, NesstSession, read_nam},
+  scan::{ScanOptions, scan},
+  screenshot::ScreenshotOptions,
+  text::TextTable,
+  tiled::TiledMap,
+  render::{render_tiles_with_graduations},
+  repack::RepackOptions,
+  pal::{TilePalette, read_palette_from_bytes}
 };
 use clap::{Args, Parser, Subcommand};
 use clap_num::maybe_hex;
@@ -31,11 +42,65 @@ enum Commands {
 
   /// Parse .rlts file
   Atlas(AtlasArgs),
+
+  /// Check an atlas against the sprite constraints of the NES
+  Lint(LintArgs),
+
+  /// List the sides and files of a Famicom Disk System image
+  Info(InfoArgs),
+
+  /// Look for regions of a binary that look like CHR data
+  Scan(ScanArgs),
+
+  /// Find the tiles of a binary that produce the 8x8 cells of a png
+  Find(FindArgs),
+
+  /// Build an atlas reproducing a screenshot
+  Build(BuildArgs),
+
+  /// Convert a png to CHR, with an atlas reproducing it
+  Import(ImportArgs),
+
+  /// Report duplicate, near-duplicate and shared tiles of one or many binaries
+  Fingerprint(FingerprintArgs),
+
+  /// Compare the CHR of two binaries tile by tile
+  Diff(DiffArgs),
+
+  /// Pack the tiles used by an atlas into a new CHR
+  Repack(RepackArgs),
+
+  /// Export the groups of an atlas as metasprite tables
+  Metasprites(MetaspritesArgs),
+
+  /// Read metasprite tables from a binary into groups of an atlas
+  ImportMetasprites(ImportMetaspritesArgs),
+
+  /// Create an atlas from a NES Screen Tool / NEXXT metasprite bank or session
+  NesstImport(NesstImportArgs),
+
+  /// Export an atlas to a NES Screen Tool / NEXXT metasprite bank or session
+  NesstExport(NesstExportArgs),
+
+  /// Render the CHR of an atlas as a Tiled tileset
+  TiledTileset(TiledTilesetArgs),
+
+  /// Export an atlas as a Tiled map, with its tilesets
+  TiledExport(TiledExportArgs),
+
+  /// Add the tiles of a Tiled map to an atlas
+  TiledImport(TiledImportArgs),
+
+  /// Export the groups and animations of an atlas as a Godot SpriteFrames resource
+  Godot(GodotArgs),
+
+  /// Render a string, or text from a binary, with a text table and the font tiles of an atlas
+  Text(TextArgs),
 }
 
 #[derive(Args)]
 struct GetArgs {
-  /// path to rom, parsed with nes_rom crate
+  /// path to rom, can point into a zip archive (archive.zip or archive.zip#inner.nes)
   #[arg()]
   bin_path: String,
 
@@ -50,6 +115,14 @@ struct GetArgs {
   /// length of bytes to read
   #[arg(short, value_parser=maybe_hex::<usize>, default_value="0x1000")]
   length: usize,
+
+  /// tile format (nes, 1bpp, gb, snes_2bpp, snes_4bpp, snes_8bpp, sms, gg, genesis, md)
+  #[arg(short, long, default_value="nes")]
+  format: TileFormat,
+
+  /// decompress the data starting at the position first (rle, konami_rle, lzss, lz10), the length is then the decompressed length
+  #[arg(short, long)]
+  compression: Option<Compression>,
 }
 
 #[derive(Args)]
@@ -61,6 +134,495 @@ struct AtlasArgs {
   /// output path
   #[arg()]
   output_path: String,
+
+  /// render in greyscale (PPUMASK bit 0)
+  #[arg(long)]
+  greyscale: bool,
+
+  /// emphasize red (PPUMASK bit 5)
+  #[arg(long)]
+  emphasis_red: bool,
+
+  /// emphasize green (PPUMASK bit 6)
+  #[arg(long)]
+  emphasis_green: bool,
+
+  /// emphasize blue (PPUMASK bit 7)
+  #[arg(long)]
+  emphasis_blue: bool,
+}
+
+#[derive(Args)]
+struct LintArgs {
+  /// atlas to check
+  #[arg()]
+  atlas_path: String,
+
+  /// print issues as json
+  #[arg(long)]
+  json: bool,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+  /// .fds image
+  #[arg()]
+  bin_path: String,
+}
+
+#[derive(Args)]
+struct ScanArgs {
+  /// binary to scan
+  #[arg()]
+  bin_path: String,
+
+  /// tile format (nes, 1bpp, gb, snes_2bpp, snes_4bpp, snes_8bpp, sms, gg, genesis, md)
+  #[arg(short, long, default_value="nes")]
+  format: TileFormat,
+
+  /// number of tiles scored together
+  #[arg(short, long, default_value="16")]
+  window: usize,
+
+  /// minimum confidence, from 0 to 1
+  #[arg(short, long, default_value="0.6")]
+  threshold: f32,
+
+  /// render every candidate to a png in this folder
+  #[arg(short, long)]
+  render: Option<String>,
+
+  /// print candidates as json
+  #[arg(long)]
+  json: bool,
+}
+
+#[derive(Args)]
+struct FindArgs {
+  /// png to look for, cut in 8x8 cells from its top left corner
+  #[arg()]
+  png_path: String,
+
+  /// binary to search
+  #[arg()]
+  bin_path: String,
+
+  /// tile format (nes, 1bpp, gb, snes_2bpp, snes_4bpp, snes_8bpp, sms, gg, genesis, md)
+  #[arg(short, long, default_value="nes")]
+  format: TileFormat,
+
+  /// distance in bytes between two searched addresses, defaults to the size of a tile
+  #[arg(short, long)]
+  alignment: Option<usize>,
+
+  /// print matches as json
+  #[arg(long)]
+  json: bool,
+}
+
+#[derive(Args)]
+struct BuildArgs {
+  /// screenshot to reproduce
+  #[arg()]
+  png_path: String,
+
+  /// atlas giving the binary, region and palette to use, its data are replaced
+  #[arg()]
+  atlas_path: String,
+
+  /// output path of the built atlas
+  #[arg()]
+  output_path: String,
+
+  /// x offset of the 8x8 grid, searched if neither offset is given
+  #[arg(short = 'x', long)]
+  offset_x: Option<u32>,
+
+  /// y offset of the 8x8 grid, searched if neither offset is given
+  #[arg(short = 'y', long)]
+  offset_y: Option<u32>,
+
+  /// also look for sprites off the grid
+  #[arg(short, long)]
+  sprites: bool,
+}
+
+#[derive(Args)]
+struct ImportArgs {
+  /// png to convert
+  #[arg()]
+  png_path: String,
+
+  /// .pal file the png is quantized to
+  #[arg()]
+  palette_path: String,
+
+  /// output path of the CHR
+  #[arg()]
+  chr_path: String,
+
+  /// output path of the atlas
+  #[arg()]
+  atlas_path: String,
+
+  /// tile format (nes, 1bpp, gb, snes_2bpp, snes_4bpp, snes_8bpp, sms, gg, genesis, md)
+  #[arg(short, long, default_value="nes")]
+  format: TileFormat,
+
+  /// maximum number of sub-palettes
+  #[arg(short, long, default_value="4")]
+  sub_palettes: usize,
+
+  /// palette index of colour 0, defaults to transparent if the png has transparent pixels, or to its most used colour
+  #[arg(short, long, value_parser=maybe_hex::<usize>)]
+  backdrop: Option<usize>,
+
+  /// keep tiles that are flips of other tiles
+  #[arg(long)]
+  no_flips: bool,
+
+  /// print the tiles that could not be imported exactly as json
+  #[arg(long)]
+  json: bool,
+}
+
+#[derive(Args)]
+struct FingerprintArgs {
+  /// binaries to compare
+  #[arg(required = true)]
+  bin_paths: Vec<String>,
+
+  /// tile format (nes, 1bpp, gb, snes_2bpp, snes_4bpp, snes_8bpp, sms, gg, genesis, md)
+  #[arg(short, long, default_value="nes")]
+  format: TileFormat,
+
+  /// also report tiles differing by at most this many pixels
+  #[arg(short, long, default_value="0")]
+  distance: usize,
+
+  /// tiles that are flips or transpositions of each other are different
+  #[arg(long)]
+  no_transforms: bool,
+
+  /// include tiles of a single colour
+  #[arg(long)]
+  keep_blank: bool,
+
+  /// print the report as json
+  #[arg(long)]
+  json: bool,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+  /// first binary
+  #[arg()]
+  a_path: String,
+
+  /// second binary
+  #[arg()]
+  b_path: String,
+
+  /// position in hexadecimal to start reading the first binary from
+  #[arg(short, value_parser=maybe_hex::<u64>, default_value="0x0000")]
+  position: u64,
+
+  /// position in hexadecimal to start reading the second binary from, defaults to the first position
+  #[arg(short = 'q', long, value_parser=maybe_hex::<u64>)]
+  position_b: Option<u64>,
+
+  /// length of bytes to compare, defaults to the rest of each binary
+  #[arg(short, value_parser=maybe_hex::<usize>)]
+  length: Option<usize>,
+
+  /// tile format (nes, 1bpp, gb, snes_2bpp, snes_4bpp, snes_8bpp, sms, gg, genesis, md)
+  #[arg(short, long, default_value="nes")]
+  format: TileFormat,
+
+  /// render both regions and an overlay of the changed pixels to this png
+  #[arg(short, long)]
+  output: Option<String>,
+
+  /// print changed tiles as json
+  #[arg(long)]
+  json: bool,
+}
+
+#[derive(Args)]
+struct RepackArgs {
+  /// atlas to repack
+  #[arg()]
+  atlas_path: String,
+
+  /// output path of the packed CHR
+  #[arg()]
+  chr_path: String,
+
+  /// output path of the repacked atlas
+  #[arg()]
+  output_path: String,
+
+  /// keep tiles that are flips of other tiles
+  #[arg(long)]
+  no_flips: bool,
+}
+
+#[derive(Args)]
+struct MetaspritesArgs {
+  /// atlas to export
+  #[arg()]
+  atlas_path: String,
+
+  /// output path, printed if not given
+  #[arg()]
+  output_path: Option<String>,
+
+  /// source format (ca65, asm6, neslib)
+  #[arg(short, long, default_value="ca65")]
+  format: MetaspriteFormat,
+}
+
+#[derive(Args)]
+struct ImportMetaspritesArgs {
+  /// atlas to add the groups to
+  #[arg()]
+  atlas_path: String,
+
+  /// binary containing the tables
+  #[arg()]
+  bin_path: String,
+
+  /// output path of the atlas
+  #[arg()]
+  output_path: String,
+
+  /// position in hexadecimal of the first table
+  #[arg(short, value_parser=maybe_hex::<usize>)]
+  position: usize,
+
+  /// number of consecutive tables to read
+  #[arg(short, long, default_value="1")]
+  count: usize,
+
+  /// name of the group, numbered when reading more than one table
+  #[arg(short, long, default_value="metasprite")]
+  name: String,
+
+  /// toml file describing the record layout, neslib's layout if not given
+  #[arg(short, long)]
+  layout: Option<String>,
+
+  /// colours 1 to 3 of a sprite sub-palette in hexadecimal (like 16,27,18), repeat for sub-palettes 1 to 3
+  #[arg(short, long, value_parser=parse_sub_palette)]
+  sub_palette: Vec<[usize; 3]>,
+}
+
+#[derive(Args)]
+struct NesstImportArgs {
+  /// metasprite bank (.msb) or session (.nss)
+  #[arg()]
+  input_path: String,
+
+  /// palette file of the atlas
+  #[arg()]
+  palette_path: String,
+
+  /// output path of the atlas, the nametable is written next to it as a .nam
+  #[arg()]
+  output_path: String,
+
+  /// chr of the metasprites, written there when reading a session
+  #[arg(short, long)]
+  chr: String,
+
+  /// NES Screen Tool palette (.pal) of the bank, its sprite sub-palettes are used if it has any
+  #[arg(short, long)]
+  pal: Option<String>,
+
+  /// nametable (.nam, raw, names only or rle) shown as the background of the bank
+  #[arg(short, long)]
+  nam: Option<String>,
+}
+
+#[derive(Args)]
+struct NesstExportArgs {
+  /// atlas to export
+  #[arg()]
+  atlas_path: String,
+
+  /// output path, a metasprite bank (.msb) or a session (.nss)
+  #[arg()]
+  output_path: String,
+
+  /// also write the palette (.pal) of a bank there
+  #[arg(short, long)]
+  pal: Option<String>,
+
+  /// also write the nametable background (.nam) of a bank there
+  #[arg(short, long)]
+  nam: Option<String>,
+
+  /// x of the origin of the metasprites
+  #[arg(short='x', long, default_value="64")]
+  grid_x: u8,
+
+  /// y of the origin of the metasprites
+  #[arg(short='y', long, default_value="64")]
+  grid_y: u8,
+}
+
+#[derive(Args)]
+struct TiledTilesetArgs {
+  /// atlas to read the CHR and palette from
+  #[arg()]
+  atlas_path: String,
+
+  /// output path of the tileset (.tsx), the sheet is written next to it as a .png
+  #[arg()]
+  output_path: String,
+
+  /// colours 1 to 3 in hexadecimal (like 16,27,18)
+  #[arg(short, long, value_parser=parse_sub_palette, default_value="00,10,30")]
+  sub_palette: [usize; 3],
+
+  /// palette index of colour 0, transparent if not given
+  #[arg(short, long, value_parser=maybe_hex::<usize>)]
+  backdrop: Option<usize>,
+
+  /// named source to read the CHR from, the main source if not given
+  #[arg(long)]
+  source: Option<String>,
+}
+
+#[derive(Args)]
+struct TiledExportArgs {
+  /// atlas to export
+  #[arg()]
+  atlas_path: String,
+
+  /// output path of the map, a .tmx or a .json, the tilesets are written next to it
+  #[arg()]
+  output_path: String,
+}
+
+#[derive(Args)]
+struct TiledImportArgs {
+  /// atlas to add the tiles to
+  #[arg()]
+  atlas_path: String,
+
+  /// map to read, a .tmx or a .json with csv layers
+  #[arg()]
+  map_path: String,
+
+  /// output path of the atlas
+  #[arg()]
+  output_path: String,
+}
+
+#[derive(Args)]
+struct GodotArgs {
+  /// atlas to export
+  #[arg()]
+  atlas_path: String,
+
+  /// output path of the texture, a .png
+  #[arg()]
+  texture_path: String,
+
+  /// output path of the SpriteFrames resource, a .tres
+  #[arg()]
+  output_path: String,
+
+  /// path of the texture in the Godot project, res://<texture file name> by default
+  #[arg(short, long)]
+  res_path: Option<String>,
+}
+
+#[derive(Args)]
+struct TextArgs {
+  /// atlas to read the font and palette from, its data are kept
+  #[arg()]
+  atlas_path: String,
+
+  /// text table (.tbl) mapping bytes to characters
+  #[arg()]
+  table_path: String,
+
+  /// output path, a .png, or a .toml to write the atlas
+  #[arg()]
+  output_path: String,
+
+  /// string to encode with the table, \n starts a new line and [XX] writes a byte as it is
+  #[arg(short, long, required_unless_present="bin_path")]
+  text: Option<String>,
+
+  /// binary to read text from instead, can point into a zip archive (archive.zip or archive.zip#inner.nes)
+  #[arg(long)]
+  bin_path: Option<String>,
+
+  /// position in hexadecimal of the text in the binary
+  #[arg(short, value_parser=maybe_hex::<u64>, default_value="0x0000")]
+  position: u64,
+
+  /// length of bytes to read, the text stops earlier at an end of string or at the end of the binary
+  #[arg(short, value_parser=maybe_hex::<usize>, default_value="0x100")]
+  length: usize,
+
+  /// index of the tile drawn for byte 00
+  #[arg(short, long, value_parser=maybe_hex::<usize>, default_value="0x0")]
+  offset: usize,
+
+  /// position of the first tile
+  #[arg(short, default_value="0")]
+  x: u32,
+
+  /// position of the first tile
+  #[arg(short, default_value="0")]
+  y: u32,
+
+  /// number of tiles after which lines wrap
+  #[arg(short, long)]
+  columns: Option<usize>,
+
+  /// colours 1 to 3 in hexadecimal (like 16,27,18)
+  #[arg(short, long, value_parser=parse_sub_palette, default_value="00,10,30")]
+  sub_palette: [usize; 3],
+
+  /// palette index of colour 0, transparent if not given
+  #[arg(short, long, value_parser=maybe_hex::<usize>)]
+  backdrop: Option<usize>,
+
+  /// named source to read the font from, the main source if not given
+  #[arg(long)]
+  source: Option<String>,
+}
+
+/// parses three hexadecimal colours separated by commas
+fn parse_sub_palette(s: &str) -> Result<[usize; 3], String> {
+  let colors = s
+    .split(',')
+    .map(|c| usize::from_str_radix(c.trim().trim_start_matches("0x"), 16).map_err(|e| e.to_string()))
+    .collect::<Result<Vec<usize>, String>>()?;
+  colors.try_into().map_err(|_| format!("expected 3 colours, got {}", s))
+}
+
+/// reads a region of a binary, to the end of the binary without a length, in whole tiles
+fn read_tile_region(path: &str, position: u64, length: Option<usize>, tile_size: usize) -> Vec<u8> {
+  match length {
+    Some(length) => read_region(path, position, length).unwrap(),
+    None => {
+      let mut bytes = read_binary(path).unwrap();
+      let mut bytes = bytes.split_off((position as usize).min(bytes.len()));
+      bytes.truncate(bytes.len() - bytes.len() % tile_size);
+      bytes
+    }
+  }
+}
+
+/// formats the tiles of a group as binary:offset
+fn group_locations(g: &TileGroup) -> String {
+  g.locations.iter().map(|l| format!("{}:{:06X}", l.binary, l.offset)).collect::<Vec<_>>().join(" ")
 }
 
 pub fn main() {
@@ -70,20 +632,497 @@ pub fn main() {
 
   match &cli.command {
     Commands::Get(args) => {
-      let mut file = File::open(args.bin_path.clone()).unwrap();
-      file.seek(SeekFrom::Start(args.position)).unwrap();
-      let mut buf = vec![0; args.length];
-      file.read_exact(&mut buf).unwrap();
+      let codec = args.format.codec();
+
+      let buf = match args.compression {
+        Some(compression) => {
+          let bytes = read_binary(&args.bin_path).unwrap();
+          let Some(compressed) = bytes.get(args.position as usize..) else {
+            eprintln!("position {:#X} is past the end of the binary ({:#X} bytes)", args.position, bytes.len());
+            std::process::exit(1);
+          };
+          let decompressed = compression.decompress(compressed, args.length).unwrap();
+          println!(
+            "decompressed {:#X} bytes from {:#X} compressed bytes ({:#X}-{:#X})",
+            decompressed.data.len(), decompressed.consumed, args.position, args.position as usize + decompressed.consumed
+          );
 
-      let chrs = read_bytes(buf).unwrap();
-      let pals = vec![ChrPalette::default(); chrs.len()];
-      render_patterns_with_graduations(args.output_path.clone(), chrs, pals).unwrap();
+          let mut data = decompressed.data;
+          data.truncate(args.length.min(data.len() - data.len() % codec.bytes_per_tile()));
+          data
+        }
+        None => read_region(&args.bin_path, args.position, args.length).unwrap(),
+      };
+      let chrs = read_tiles(&buf, &*codec).unwrap();
+      let pals = vec![TilePalette::greyscale(codec.bits_per_pixel()); chrs.len()];
+      render_tiles_with_graduations(args.output_path.clone(), chrs, pals).unwrap();
     }
 
     Commands::Atlas(args) => {
       let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
-      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let mut atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      atlas.mask.greyscale |= args.greyscale;
+      atlas.mask.emphasis_red |= args.emphasis_red;
+      atlas.mask.emphasis_green |= args.emphasis_green;
+      atlas.mask.emphasis_blue |= args.emphasis_blue;
       atlas.render_image(args.output_path.clone()).unwrap();
     }
+
+    Commands::Lint(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let issues = atlas.lint().unwrap();
+
+      if args.json {
+        println!("{}", serde_json::to_string_pretty(&issues).unwrap());
+      } else {
+        for issue in &issues {
+          println!("{}", issue);
+        }
+      }
+
+      if !issues.is_empty() {
+        std::process::exit(1);
+      }
+    }
+
+    Commands::Scan(args) => {
+      let bytes = read_binary(&args.bin_path).unwrap();
+      let options = ScanOptions {
+        format: args.format,
+        window: args.window,
+        threshold: args.threshold,
+      };
+      let candidates = scan(&bytes, &options);
+
+      if args.json {
+        println!("{}", serde_json::to_string_pretty(&candidates).unwrap());
+      } else {
+        for c in &candidates {
+          println!("{:06X}-{:06X} length {:06X} confidence {:.2}", c.start, c.start + c.length, c.length, c.confidence);
+        }
+      }
+
+      if let Some(folder) = &args.render {
+        std::fs::create_dir_all(folder).unwrap();
+        let codec = args.format.codec();
+        for c in &candidates {
+          let chrs = read_tiles(&bytes[c.start..c.start + c.length], &*codec).unwrap();
+          let pals = vec![TilePalette::greyscale(codec.bits_per_pixel()); chrs.len()];
+          let path = format!("{}/{:06X}.png", folder, c.start);
+          render_tiles_with_graduations(path, chrs, pals).unwrap();
+        }
+      }
+    }
+
+    Commands::Find(args) => {
+      let image = image::open(&args.png_path).unwrap().to_rgba8();
+      let bytes = read_binary(&args.bin_path).unwrap();
+      let options = FindOptions {
+        format: args.format,
+        alignment: args.alignment,
+      };
+      let matches = find_tiles(&image, &bytes, &options);
+
+      if args.json {
+        println!("{}", serde_json::to_string_pretty(&matches).unwrap());
+      } else {
+        for m in &matches {
+          let t = m.transform;
+          println!(
+            "cell ({}, {}): offset {:06X} tile {:X}{}{}{}",
+            m.x, m.y, m.offset, m.tile_index,
+            if t.transpose { " transpose" } else { "" },
+            if t.flip_x { " flip_x" } else { "" },
+            if t.flip_y { " flip_y" } else { "" }
+          );
+        }
+      }
+    }
+
+    Commands::Build(args) => {
+      let image = image::open(&args.png_path).unwrap().to_rgba8();
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let options = ScreenshotOptions {
+        grid_offset: match (args.offset_x, args.offset_y) {
+          (None, None) => None,
+          (x, y) => Some((x.unwrap_or(0), y.unwrap_or(0))),
+        },
+        sprites: args.sprites,
+      };
+      let built = atlas.build_from_screenshot(&image, &options).unwrap();
+
+      println!("grid offset ({}, {}), {} tiles", built.grid_offset.0, built.grid_offset.1, built.atlas.data.len());
+      for (x, y) in &built.unmatched {
+        println!("unmatched cell ({}, {})", x, y);
+      }
+
+      std::fs::write(&args.output_path, toml::to_string(&built.atlas).unwrap()).unwrap();
+    }
+
+    Commands::Import(args) => {
+      let image = image::open(&args.png_path).unwrap().to_rgba8();
+      let pal = read_palette_from_bytes(std::fs::read(&args.palette_path).unwrap()).unwrap();
+      let options = ImportOptions {
+        format: args.format,
+        sub_palettes: args.sub_palettes,
+        backdrop: args.backdrop,
+        flips: !args.no_flips,
+      };
+      let imported = import_image(&image, &pal, &options);
+
+      if args.json {
+        println!("{}", serde_json::to_string_pretty(&imported.issues).unwrap());
+      } else {
+        println!("{} tiles, {} cells", imported.tiles.len(), imported.data.len());
+        match imported.backdrop {
+          Some(b) => println!("backdrop {:02X}", b),
+          None => println!("backdrop transparent"),
+        }
+        for (i, sp) in imported.sub_palettes.iter().enumerate() {
+          println!("sub-palette {}: {:02X?}", i, sp);
+        }
+        for issue in &imported.issues {
+          println!("{}", issue);
+        }
+      }
+
+      std::fs::write(&args.chr_path, &imported.chr).unwrap();
+      let atlas = imported.to_atlas(args.chr_path.clone(), args.palette_path.clone());
+      std::fs::write(&args.atlas_path, toml::to_string(&atlas).unwrap()).unwrap();
+    }
+
+    Commands::Fingerprint(args) => {
+      let mut index = FingerprintIndex::new(FingerprintOptions {
+        format: args.format,
+        transforms: !args.no_transforms,
+        skip_blank: !args.keep_blank,
+      });
+      for path in &args.bin_paths {
+        index.add(path.clone(), &read_binary(path).unwrap());
+      }
+
+      let duplicates = index.duplicates();
+      let shared = index.shared();
+      let near = match args.distance {
+        0 => Vec::new(),
+        d => index.near_duplicates(d),
+      };
+
+      if args.json {
+        let report = serde_json::json!({
+          "binaries": index.binaries,
+          "tiles": index.groups().iter().map(|g| g.locations.len()).sum::<usize>(),
+          "unique": index.groups().len(),
+          "duplicates": duplicates,
+          "shared": shared,
+          "near_duplicates": near,
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+      } else {
+        for (i, name) in index.binaries.iter().enumerate() {
+          println!("binary {}: {}", i, name);
+        }
+        println!(
+          "{} tiles, {} unique",
+          index.groups().iter().map(|g| g.locations.len()).sum::<usize>(), index.groups().len()
+        );
+
+        println!("\n{} duplicated tiles", duplicates.len());
+        for g in &duplicates {
+          println!("  {} x{}: {}", g.fingerprint, g.locations.len(), group_locations(g));
+        }
+
+        if index.binaries.len() > 1 {
+          println!("\n{} tiles shared between binaries", shared.len());
+          for g in &shared {
+            println!("  {}: {}", g.fingerprint, group_locations(g));
+          }
+        }
+
+        if args.distance > 0 {
+          println!("\n{} near duplicates", near.len());
+          for n in &near {
+            println!("  {} ~ {}, {} pixels: {} ~ {}", n.a.fingerprint, n.b.fingerprint, n.distance, group_locations(&n.a), group_locations(&n.b));
+          }
+        }
+      }
+    }
+
+    Commands::Diff(args) => {
+      let size = args.format.codec().bytes_per_tile();
+      let position_b = args.position_b.unwrap_or(args.position);
+      let a = read_tile_region(&args.a_path, args.position, args.length, size);
+      let b = read_tile_region(&args.b_path, position_b, args.length, size);
+      let diff = diff_chr(&a, &b, args.format).unwrap();
+
+      if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff.changed).unwrap());
+      } else {
+        for d in &diff.changed {
+          match d.pixels {
+            Some(pixels) => println!(
+              "tile {:X}: {:06X} / {:06X}, {} pixels, {} bytes",
+              d.index, args.position as usize + d.offset, position_b as usize + d.offset, pixels, d.bytes
+            ),
+            None if d.index < diff.a.len() => println!("tile {:X}: {:06X}, only in the first binary", d.index, args.position as usize + d.offset),
+            None => println!("tile {:X}: {:06X}, only in the second binary", d.index, position_b as usize + d.offset),
+          }
+        }
+        println!("{} of {} tiles changed", diff.changed.len(), diff.a.len().max(diff.b.len()));
+      }
+
+      if let Some(output) = &args.output {
+        diff.render(output.clone()).unwrap();
+      }
+
+      if !diff.changed.is_empty() {
+        std::process::exit(1);
+      }
+    }
+
+    Commands::Repack(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let repacked = atlas.repack(args.chr_path.clone(), &RepackOptions { flips: !args.no_flips }).unwrap();
+
+      println!("packed {} tiles for {} data", repacked.tiles.len(), repacked.atlas.data.len());
+      for (i, (source, index)) in repacked.origins.iter().enumerate() {
+        println!("  {:X}: {} {:X}", i, source.as_deref().unwrap_or("main"), index);
+      }
+
+      std::fs::write(&args.chr_path, &repacked.chr).unwrap();
+      std::fs::write(&args.output_path, toml::to_string(&repacked.atlas).unwrap()).unwrap();
+    }
+
+    Commands::Metasprites(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let source = atlas.export_metasprites(args.format).unwrap();
+
+      match &args.output_path {
+        Some(path) => std::fs::write(path, source).unwrap(),
+        None => print!("{}", source),
+      }
+    }
+
+    Commands::ImportMetasprites(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let mut atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let bytes = read_binary(&args.bin_path).unwrap();
+      let layout: MetaspriteLayout = match &args.layout {
+        Some(path) => toml::from_str(&read_to_string(path).unwrap()).unwrap(),
+        None => MetaspriteLayout::default(),
+      };
+      // greys for the sub-palettes that are not given
+      let mut sub_palettes = args.sub_palette.clone();
+      sub_palettes.resize(4, [0x00, 0x10, 0x30]);
+
+      let mut position = args.position;
+      for i in 0..args.count {
+        let name = match args.count {
+          1 => args.name.clone(),
+          _ => format!("{}_{}", args.name, i),
+        };
+        let size = atlas.import_metasprite(&name, &bytes, position, &layout, &sub_palettes, None).unwrap();
+        println!("{}: {:06X}-{:06X}", name, position, position + size);
+        position += size;
+      }
+
+      std::fs::write(&args.output_path, toml::to_string(&atlas).unwrap()).unwrap();
+    }
+
+    Commands::NesstImport(args) => {
+      let (bank, palette, nametable) = match args.input_path.ends_with(".nss") {
+        true => {
+          let session = NesstSession::from_text(&read_to_string(&args.input_path).unwrap()).unwrap();
+          std::fs::write(&args.chr, &session.chr).unwrap();
+          (session.metasprites.unwrap_or_default(), session.palette, session.nametable)
+        }
+        false => (
+          MetaspriteBank::from_bytes(&read_binary(&args.input_path).unwrap()).unwrap(),
+          args.pal.as_ref().map(|p| NesstPalette::from_bytes(&read_binary(p).unwrap()).unwrap()),
+          args.nam.as_ref().map(|n| read_nam(&read_binary(n).unwrap()).unwrap()),
+        ),
+      };
+
+      // greys without a palette, the background sub-palettes for a palette without sprites
+      let sub_palettes = match palette {
+        Some(p) => p.sprites.unwrap_or(p.background).to_vec(),
+        None => vec![[0x00, 0x10, 0x30]; 4],
+      };
+      let chr_length = read_binary(&args.chr).unwrap().len();
+      let source = AtlasSource {
+        binary: args.chr.clone(),
+        start: 0,
+        length: chr_length,
+        banks: Vec::new(),
+        format: TileFormat::Nes,
+        fds: None,
+        compression: None,
+      };
+      let mut atlas = bank.to_atlas(source, args.palette_path.clone(), &sub_palettes).unwrap();
+
+      if let Some(nametable) = nametable {
+        let nam_path = std::path::Path::new(&args.output_path).with_extension("nam").to_string_lossy().to_string();
+        std::fs::write(&nam_path, nametable.to_bytes()).unwrap();
+        let palette = palette.unwrap_or(NesstPalette { backdrop: 0x0F, background: [[0x00, 0x10, 0x30]; 4], sprites: None });
+        atlas.background = Some(AtlasBackground::Nametable(palette.to_background(nam_path, 0, 0)));
+      }
+
+      println!("{} metasprites, {} sprites", atlas.groups.len(), atlas.data.len());
+      std::fs::write(&args.output_path, toml::to_string(&atlas).unwrap()).unwrap();
+    }
+
+    Commands::NesstExport(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+
+      match args.output_path.ends_with(".nss") {
+        true => {
+          let session = NesstSession::from_atlas(&atlas, args.grid_x, args.grid_y).unwrap();
+          std::fs::write(&args.output_path, session.to_text()).unwrap();
+        }
+        false => {
+          let bank = MetaspriteBank::from_atlas(&atlas, args.grid_x, args.grid_y).unwrap();
+          std::fs::write(&args.output_path, bank.to_bytes()).unwrap();
+        }
+      }
+
+      if let Some(pal) = &args.pal {
+        std::fs::write(pal, atlas.get_nesst_palette().unwrap().to_bytes()).unwrap();
+      }
+      if let Some(nam) = &args.nam {
+        let Some(AtlasBackground::Nametable(n)) = &atlas.background else {
+          eprintln!("the atlas has no nametable background");
+          std::process::exit(1);
+        };
+        std::fs::write(nam, n.get_nametable().unwrap().to_bytes()).unwrap();
+      }
+    }
+
+    Commands::TiledTileset(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let path = std::path::Path::new(&args.output_path);
+      let name = path.file_stem().unwrap().to_string_lossy().to_string();
+      let image = format!("{}.png", name);
+
+      let template = AtlasData {
+        source: args.source.clone(),
+        c0: args.sub_palette[0],
+        c1: args.sub_palette[1],
+        c2: args.sub_palette[2],
+        cbg: args.backdrop,
+        ..Default::default()
+      };
+      let (tileset, img) = atlas.get_tiled_tileset(&name, &image, &template).unwrap();
+      img.save(path.with_file_name(&image)).unwrap();
+      std::fs::write(path, tileset.to_tsx()).unwrap();
+      println!("{} tiles", tileset.tile_count);
+    }
+
+    Commands::TiledExport(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let path = std::path::Path::new(&args.output_path);
+      let name = path.file_stem().unwrap().to_string_lossy().to_string();
+
+      let export = atlas.to_tiled(&name).unwrap();
+      for ((r, tileset), img) in export.map.tilesets.iter().zip(&export.tilesets).zip(&export.images) {
+        img.save(path.with_file_name(&tileset.image)).unwrap();
+        std::fs::write(path.with_file_name(&r.source), tileset.to_tsx()).unwrap();
+      }
+      let map = match args.output_path.ends_with(".json") {
+        true => serde_json::to_string_pretty(&export.map).unwrap(),
+        false => export.map.to_tmx(),
+      };
+      std::fs::write(path, map).unwrap();
+      println!("{} tilesets, {} layers", export.tilesets.len(), export.map.layers.len());
+    }
+
+    Commands::TiledImport(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let mut atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let map_str = read_to_string(&args.map_path).unwrap();
+      let map: TiledMap = match args.map_path.ends_with(".json") {
+        true => serde_json::from_str(&map_str).unwrap(),
+        false => TiledMap::from_tmx(&map_str).unwrap(),
+      };
+      // tilesets are relative to the map
+      let path = std::path::Path::new(&args.map_path);
+      let tilesets = map
+        .get_tilesets(|source| read_to_string(path.with_file_name(source)).map_err(chr_reatlas::Error::IOError))
+        .unwrap();
+
+      let count = atlas.import_tiled(&map, &tilesets).unwrap();
+      println!("{} data", count);
+      std::fs::write(&args.output_path, toml::to_string(&atlas).unwrap()).unwrap();
+    }
+
+    Commands::Godot(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let res_path = args.res_path.clone().unwrap_or_else(|| {
+        let name = std::path::Path::new(&args.texture_path).file_name().unwrap();
+        format!("res://{}", name.to_string_lossy())
+      });
+
+      let animations = atlas.get_godot_animations().unwrap();
+      std::fs::write(&args.texture_path, atlas.get_godot_texture().unwrap()).unwrap();
+      std::fs::write(&args.output_path, atlas.export_godot_sprite_frames(&res_path).unwrap()).unwrap();
+      let frames: usize = animations.iter().map(|a| a.frames.len()).sum();
+      println!("{} animations, {} frames", animations.len(), frames);
+    }
+
+    Commands::Text(args) => {
+      let atlas_str = read_to_string(args.atlas_path.clone()).unwrap();
+      let mut atlas: Atlas = toml::from_str(&atlas_str).unwrap();
+      let table = TextTable::from_tbl(&read_to_string(&args.table_path).unwrap()).unwrap();
+
+      let bytes = match &args.text {
+        Some(text) => table.encode(&text.replace("\\n", "\n")).unwrap(),
+        None => {
+          let bytes = read_binary(args.bin_path.as_ref().unwrap()).unwrap();
+          let start = (args.position as usize).min(bytes.len());
+          bytes[start..(start + args.length).min(bytes.len())].to_vec()
+        }
+      };
+      println!("{}", table.decode(&bytes));
+
+      let template = AtlasData {
+        source: args.source.clone(),
+        chr_index: args.offset,
+        c0: args.sub_palette[0],
+        c1: args.sub_palette[1],
+        c2: args.sub_palette[2],
+        x: args.x,
+        y: args.y,
+        cbg: args.backdrop,
+        ..Default::default()
+      };
+      let count = atlas.add_text(&table, &bytes, &template, args.columns);
+      match args.output_path.ends_with(".toml") {
+        true => std::fs::write(&args.output_path, toml::to_string(&atlas).unwrap()).unwrap(),
+        false => atlas.render_image(args.output_path.clone()).unwrap(),
+      }
+      println!("{} tiles", count);
+    }
+
+    Commands::Info(args) => {
+      let bytes = read_binary(&args.bin_path).unwrap();
+      let image = FdsImage::from_bytes(&bytes).unwrap();
+
+      for (s, side) in image.sides.iter().enumerate() {
+        println!("side {} ({}), {} files, {} visible", s, side.game_name, side.files.len(), side.file_amount);
+        for (f, file) in side.files.iter().enumerate() {
+          println!(
+            "  file {:>2}: #{:02X} id {:02X} {:<8} {:<3} load {:04X} size {:04X} offset {:06X}",
+            f, file.number, file.id, file.name, file.kind.to_string(), file.load_address, file.data.len(), file.offset
+          );
+        }
+      }
+    }
   }
 }
@@ -1,34 +1,49 @@
 use anyhow::Result;
 use chr_reatlas::{
-  atlas::Atlas,
-  chr::read_bytes,
-  pal::{ChrPalette, read_palette_from_bytes},
-  render::get_patterns_as_png_bytes,
+  atlas::{Atlas, AtlasSource},
+  codec::TilePattern,
+  render::get_tiles_as_png_bytes,
 };
 use raylib::prelude::*;
-use std::{
-  fs::File,
-  io::{Read, Seek, SeekFrom},
-};
+use std::collections::BTreeMap;
+
+pub struct SourceDisplay {
+  pub texture: Texture2D,
+  pub chr_count: usize,
+}
 
 pub struct AtlasDisplay {
   pub binary_texture: Texture2D,
   pub atlas_texture: Option<Texture2D>,
   pub palette: Vec<[u8; 3]>,
+  pub chr_count: usize,
+  pub sources: BTreeMap<String, SourceDisplay>,
+}
+
+fn load_chrs_texture(
+  d: &mut RaylibDrawHandle,
+  thread: &RaylibThread,
+  source: &AtlasSource,
+  chrs: Vec<TilePattern>,
+) -> Result<Texture2D> {
+  let bits_per_pixel = source.format.codec().bits_per_pixel();
+  let tiles_bytes = get_tiles_as_png_bytes(chrs, bits_per_pixel)?;
+  let image = Image::load_image_from_mem(".png", &tiles_bytes)?;
+  Ok(d.load_texture_from_image(thread, &image)?)
 }
 
 impl AtlasDisplay {
   pub fn from_atlas(d: &mut RaylibDrawHandle, thread: &RaylibThread, a: &Atlas) -> Result<Self> {
-    let mut file = File::open(a.binary.clone())?;
-    file.seek(SeekFrom::Start(a.start))?;
-    let mut buf = vec![0; a.length];
-    file.read_exact(&mut buf)?;
+    let chrs = a.get_all_chrs()?;
+    let chr_count = chrs.main.len();
+    let binary_texture = load_chrs_texture(d, thread, &a.source, chrs.main)?;
 
-    let chrs = read_bytes(buf)?;
-    let pals = vec![ChrPalette::default(); chrs.len()];
-    let binary_patterns_bytes = get_patterns_as_png_bytes(chrs, pals)?;
-    let binary_image = Image::load_image_from_mem(".png", &binary_patterns_bytes)?;
-    let binary_texture = d.load_texture_from_image(thread, &binary_image)?;
+    let mut sources = BTreeMap::new();
+    for (name, source_chrs) in chrs.sources {
+      let chr_count = source_chrs.len();
+      let texture = load_chrs_texture(d, thread, &a.sources[&name], source_chrs)?;
+      sources.insert(name, SourceDisplay { texture, chr_count });
+    }
 
     let atlas_texture = match a.data.len() {
       0 => None,
@@ -39,18 +54,25 @@ impl AtlasDisplay {
       }
     };
 
-    let mut pal_file = File::open(&a.palette)?;
-    let mut pal_buf = Vec::new();
-    pal_file.read_to_end(&mut pal_buf)?;
-    let palette = read_palette_from_bytes(pal_buf)?;
+    let palette = a.get_palette()?;
 
     Ok(Self {
       binary_texture,
       atlas_texture,
       palette,
+      chr_count,
+      sources,
     })
   }
 
+  /// returns the texture and the chr count of a source, the main source if `None`
+  pub fn source(&self, name: Option<&str>) -> (&Texture2D, usize) {
+    match name.and_then(|n| self.sources.get(n)) {
+      Some(s) => (&s.texture, s.chr_count),
+      None => (&self.binary_texture, self.chr_count),
+    }
+  }
+
   pub fn regen_atlas_texture(
     &mut self,
     d: &mut RaylibDrawHandle,
@@ -244,7 +244,7 @@ impl Editor {
                   dc.draw_rectangle_rec(dest_rec, Color::new(0, 0, 0, 100));
 
                   dc.draw_texture_pro(
-                    &ad.binary_texture,
+                    ad.source(a.data[*sd].source.as_deref()).0,
                     Rectangle::new(
                       (a.data[*sd].chr_index % 16) as f32 * 8.,
                       (a.data[*sd].chr_index / 16) as f32 * 8.,
@@ -269,7 +269,7 @@ impl Editor {
                     dc.draw_rectangle_rec(dest_rec, Color::new(0, 0, 0, 100));
 
                     dc.draw_texture_pro(
-                      &ad.binary_texture,
+                      ad.source(a.data[*sd].source.as_deref()).0,
                       Rectangle::new(
                         (a.data[*sd].chr_index % 16) as f32 * 8.,
                         (a.data[*sd].chr_index / 16) as f32 * 8.,
@@ -3,7 +3,9 @@ use raylib::prelude::*;
 
 use crate::{Context, pane::Pane};
 
-pub struct PickerPanel {}
+pub struct PickerPanel {
+  source: Option<String>,
+}
 
 pub enum PickerPaneMessage {}
 
@@ -11,7 +13,7 @@ const SCALE: f32 = 3.;
 
 impl Pane<Context, PickerPaneMessage> for PickerPanel {
   fn init() -> Self {
-    Self {}
+    Self { source: None }
   }
 
   fn is_opened(&self, c: &Context) -> bool {
@@ -38,7 +40,7 @@ impl Pane<Context, PickerPaneMessage> for PickerPanel {
     d: &mut RaylibDrawHandle,
     t: &RaylibThread,
     c: &mut Context,
-    _i: bool,
+    i: bool,
   ) -> Vec<PickerPaneMessage> {
     let mut _r = Vec::new();
 
@@ -49,10 +51,38 @@ impl Pane<Context, PickerPaneMessage> for PickerPanel {
 
     d.gui_panel(self.get_rect(d), "picker");
 
+    if let Some(a) = &c.atlas {
+      if let Some(s) = &self.source {
+        if !a.sources.contains_key(s) {
+          self.source = None;
+        }
+      }
+
+      if !a.sources.is_empty() {
+        let r = self.get_rect(d);
+        let label = self.source.clone().unwrap_or(String::from("main"));
+        if d.gui_button(
+          Rectangle::new(r.x + r.width - 105., r.y + 2., 100., 20.),
+          &label,
+        ) && i
+        {
+          let names: Vec<&String> = a.sources.keys().collect();
+          self.source = match &self.source {
+            None => names.first().map(|n| n.to_string()),
+            Some(s) => names
+              .iter()
+              .position(|n| *n == s)
+              .and_then(|p| names.get(p + 1))
+              .map(|n| n.to_string()),
+          };
+        }
+      }
+    }
+
     if let Some(ad) = &mut c.atlas_display {
       if let Some(a) = &mut c.atlas {
-        let n = a.length / 0x10;
-        for j in 0..n {
+        let chr_count = ad.source(self.source.as_deref()).1;
+        for j in 0..chr_count {
           let x = j % 16;
           let y = j / 16;
 
@@ -62,6 +92,7 @@ impl Pane<Context, PickerPaneMessage> for PickerPanel {
 
           if d.gui_button(Rectangle::new(rx, ry, rs, rs), "") {
             a.data.push(AtlasData {
+              source: self.source.clone(),
               chr_index: j,
               x: 0,
               y: 0,
@@ -69,17 +100,16 @@ impl Pane<Context, PickerPaneMessage> for PickerPanel {
               c0: c.default_colors[0],
               c1: c.default_colors[1],
               c2: c.default_colors[2],
-              transpose: false,
-              flip_x: false,
-              flip_y: false,
+              ..Default::default()
             });
             ad.regen_atlas_texture(d, t, a).unwrap();
           }
         }
       }
 
-      let texture_width = ad.binary_texture.width as f32;
-      let texture_height = ad.binary_texture.height as f32;
+      let texture = ad.source(self.source.as_deref()).0;
+      let texture_width = texture.width as f32;
+      let texture_height = texture.height as f32;
 
       let mut source_rec = Rectangle::new(0., 0., texture_width, texture_height);
 
@@ -92,7 +122,7 @@ impl Pane<Context, PickerPaneMessage> for PickerPanel {
       }
 
       d.draw_texture_pro(
-        &ad.binary_texture,
+        texture,
         source_rec,
         inside_rect,
         Vector2::zero(),
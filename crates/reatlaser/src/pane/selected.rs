@@ -64,7 +64,7 @@ impl Pane<Context, SelectedPanelMessage> for SelectedPanel {
               );
 
               d.draw_texture_pro(
-                &ad.binary_texture,
+                ad.source(a.data[*sd].source.as_deref()).0,
                 Rectangle::new(
                   (a.data[*sd].chr_index % 16) as f32 * 8.,
                   (a.data[*sd].chr_index / 16) as f32 * 8.,
@@ -284,6 +284,14 @@ impl Pane<Context, SelectedPanelMessage> for SelectedPanel {
                 ad.regen_atlas_texture(d, &t, a).unwrap();
               }
 
+              if d.gui_check_box(
+                Rectangle::new(inside_rect.x + 10., inside_rect.y + 10. + 185., 20., 20.),
+                "Behind BG",
+                &mut a.data[*sd].behind_background,
+              ) {
+                ad.regen_atlas_texture(d, &t, a).unwrap();
+              }
+
               if d.gui_button(
                 Rectangle::new(
                   inside_rect.x + 10.,
@@ -1,6 +1,12 @@
 use anyhow::Result;
-use chr_reatlas::atlas::Atlas;
+use chr_reatlas::{
+  atlas::{Atlas, AtlasSource},
+  codec::TileFormat,
+  pal::PaletteFormat,
+  ppu::{PpuMask, PpuType},
+};
 use raylib::prelude::*;
+use std::collections::BTreeMap;
 
 use crate::{Context, atlas::AtlasDisplay, make_text_input, window::Window};
 
@@ -23,11 +29,24 @@ impl AtlasCreator {
     c: &mut Context,
   ) -> Result<()> {
     let new_atlas = Atlas {
-      binary: self.text_box_buffers[0].clone(),
+      source: AtlasSource {
+        binary: self.text_box_buffers[0].clone(),
+        start: u64::from_str_radix(&self.text_box_buffers[2], 16)?,
+        length: usize::from_str_radix(&self.text_box_buffers[3], 16)? * 0x10,
+        banks: Vec::new(),
+        format: TileFormat::default(),
+        fds: None,
+        compression: None,
+      },
+      sources: BTreeMap::new(),
       palette: self.text_box_buffers[1].clone(),
-      start: u64::from_str_radix(&self.text_box_buffers[2], 16)?,
-      length: usize::from_str_radix(&self.text_box_buffers[3], 16)? * 0x10,
+      palette_format: PaletteFormat::default(),
+      ppu: PpuType::default(),
+      mask: PpuMask::default(),
       data: Vec::new(),
+      background: None,
+      groups: BTreeMap::new(),
+      animations: BTreeMap::new(),
     };
 
     let atlas_display = AtlasDisplay::from_atlas(d, t, &new_atlas)?;
@@ -105,7 +124,7 @@ impl Window<Context, AtlasCreatorMessage> for AtlasCreator {
 
       d.gui_label(
         Rectangle::new(r.x + 10., r.y + 35., r.width - 20., 10.),
-        "Relative path to binary",
+        "Relative path to binary (or archive.zip#entry)",
       );
       make_text_input!(
         d,